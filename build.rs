@@ -39,4 +39,5 @@ fn main() {
     println!("cargo:rustc-link-lib=paludis_3.0");
     println!("cargo:rustc-link-lib=paludisutil_3.0");
     println!("cargo:rustc-link-lib=paludisargs_3.0");
+    println!("cargo:rustc-link-lib=paludisresolver_3.0");
 }
@@ -0,0 +1,341 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use cxx::SharedPtr;
+
+use super::bindings::paludis_package_id_sequence_new;
+use super::bindings::paludis_package_id_sequence_push_back;
+use super::bindings::paludis_packageid_fetch;
+use super::bindings::paludis_packageid_info_or_config;
+use super::bindings::paludis_packageid_install;
+use super::bindings::paludis_packageid_uninstall;
+
+use super::environment::OuputExclusivity;
+use super::OutputManager;
+use super::PackageID;
+use super::Result;
+
+/// Kinds of actions a [`PackageID`](crate::PackageID) may support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionKind {
+    /// Install a package into a repository.
+    Install,
+    /// Uninstall an installed package.
+    Uninstall,
+    /// Fetch the files needed to install a package.
+    Fetch,
+    /// Check what fetching a package would need.
+    PretendFetch,
+    /// Run sanity checks before installing a package.
+    Pretend,
+    /// Display information about a package.
+    Info,
+    /// Configure an installed package.
+    Config,
+}
+
+impl Into<u8> for ActionKind {
+    fn into(self) -> u8 {
+        match self {
+            ActionKind::Install => 0,
+            ActionKind::Uninstall => 1,
+            ActionKind::Fetch => 2,
+            ActionKind::PretendFetch => 3,
+            ActionKind::Pretend => 4,
+            ActionKind::Info => 5,
+            ActionKind::Config => 6,
+        }
+    }
+}
+
+/// How the output managers of an action are made.
+#[derive(Debug, Clone)]
+pub enum OutputManagerFactory {
+    /// Ask the [`Environment`](crate::Environment), following the paludis output.conf.
+    Environment {
+        exclusivity: OuputExclusivity,
+        /// Show a summary of the messages once the action is done.
+        summary: bool,
+    },
+    /// Write the output to a log file, see [log_path](OutputManagerFactory::log_path).
+    Log {
+        /// Path of the log without its ".out" extension, e.g. "/var/log/paludis/rust".
+        path: PathBuf,
+    },
+    /// Use the given output manager, e.g. one made with [from_sink](OutputManager::from_sink).
+    Manager(Arc<OutputManager>),
+}
+
+impl Default for OutputManagerFactory {
+    fn default() -> Self {
+        OutputManagerFactory::Environment {
+            exclusivity: OuputExclusivity::Exclusive,
+            summary: false,
+        }
+    }
+}
+
+impl OutputManagerFactory {
+    /// Path of the log file written, None if the output follows the paludis configuration.
+    pub fn log_path(&self) -> Option<PathBuf> {
+        match self {
+            OutputManagerFactory::Environment { .. } | OutputManagerFactory::Manager(_) => None,
+            OutputManagerFactory::Log { path } => {
+                let mut log = path.clone().into_os_string();
+                log.push(".out");
+                Some(PathBuf::from(log))
+            }
+        }
+    }
+
+    /// Exclusivity, summary, log path and output manager given to the action bindings.
+    fn kind(&self) -> (u8, bool, String, SharedPtr<u64>) {
+        match self {
+            OutputManagerFactory::Environment {
+                exclusivity,
+                summary,
+            } => (
+                (*exclusivity).into(),
+                *summary,
+                String::new(),
+                SharedPtr::null(),
+            ),
+            OutputManagerFactory::Log { path } => (
+                OuputExclusivity::WithOthers.into(),
+                false,
+                path.to_string_lossy().into_owned(),
+                SharedPtr::null(),
+            ),
+            OutputManagerFactory::Manager(om) => (
+                OuputExclusivity::WithOthers.into(),
+                false,
+                String::new(),
+                om.ptr.clone(),
+            ),
+        }
+    }
+}
+
+/// Which files a fetch action downloads.
+#[derive(Debug, Clone, Copy)]
+pub struct FetchParts {
+    /// Files needed to install the package.
+    pub regulars: bool,
+    /// Files of optional extras, e.g. tests data.
+    pub extras: bool,
+    /// Files not needed with the current choices.
+    pub unneeded: bool,
+}
+
+impl Default for FetchParts {
+    fn default() -> Self {
+        FetchParts {
+            regulars: true,
+            extras: true,
+            unneeded: false,
+        }
+    }
+}
+
+impl Into<u8> for FetchParts {
+    fn into(self) -> u8 {
+        (self.regulars as u8) | (self.extras as u8) << 1 | (self.unneeded as u8) << 2
+    }
+}
+
+/// Options of [`Action::Fetch`] and [`Action::PretendFetch`].
+#[derive(Debug, Clone)]
+pub struct FetchActionOptions {
+    pub fetch_parts: FetchParts,
+    /// Don't fail on files that are not fetched yet, e.g. to check what is already there.
+    pub ignore_unfetched: bool,
+    /// Skip the files that can't be mirrored.
+    pub exclude_unmirrorable: bool,
+    /// Don't check the files against the manifest.
+    pub ignore_not_in_manifest: bool,
+    /// Resume interrupted downloads.
+    pub safe_resume: bool,
+    pub output: OutputManagerFactory,
+}
+
+impl Default for FetchActionOptions {
+    fn default() -> Self {
+        FetchActionOptions {
+            fetch_parts: FetchParts::default(),
+            ignore_unfetched: false,
+            exclude_unmirrorable: false,
+            ignore_not_in_manifest: false,
+            safe_resume: true,
+            output: OutputManagerFactory::default(),
+        }
+    }
+}
+
+/// Options of [`Action::Install`] and [`Action::Pretend`].
+#[derive(Debug, Clone)]
+pub struct InstallActionOptions<'env> {
+    /// Name of the repository to install to, e.g. "installed".
+    pub destination: String,
+    /// Installed IDs replaced by this one, uninstalled once it is installed.
+    pub replacing: Vec<PackageID<'env>>,
+    pub output: OutputManagerFactory,
+}
+
+impl<'env> InstallActionOptions<'env> {
+    /// Install to the given repository, replacing nothing.
+    pub fn new(destination: &str) -> Self {
+        InstallActionOptions {
+            destination: destination.to_owned(),
+            replacing: Vec::new(),
+            output: OutputManagerFactory::default(),
+        }
+    }
+}
+
+/// Options of [`Action::Uninstall`].
+#[derive(Debug, Clone, Default)]
+pub struct UninstallActionOptions {
+    /// Space separated paths whose modified files are kept, e.g. "/etc".
+    pub config_protect: String,
+    pub output: OutputManagerFactory,
+}
+
+/// Options of [`Action::Info`] and [`Action::Config`].
+#[derive(Debug, Clone, Default)]
+pub struct SimpleActionOptions {
+    pub output: OutputManagerFactory,
+}
+
+/// An action to perform on a [`PackageID`], see [perform](PackageID::perform).
+#[derive(Debug, Clone)]
+pub enum Action<'env> {
+    Install(InstallActionOptions<'env>),
+    Uninstall(UninstallActionOptions),
+    Fetch(FetchActionOptions),
+    PretendFetch(FetchActionOptions),
+    Pretend(InstallActionOptions<'env>),
+    Info(SimpleActionOptions),
+    Config(SimpleActionOptions),
+}
+
+impl<'env> Action<'env> {
+    pub fn kind(&self) -> ActionKind {
+        match self {
+            Action::Install(_) => ActionKind::Install,
+            Action::Uninstall(_) => ActionKind::Uninstall,
+            Action::Fetch(_) => ActionKind::Fetch,
+            Action::PretendFetch(_) => ActionKind::PretendFetch,
+            Action::Pretend(_) => ActionKind::Pretend,
+            Action::Info(_) => ActionKind::Info,
+            Action::Config(_) => ActionKind::Config,
+        }
+    }
+}
+
+/// A file a fetch action couldn't get, see [fetch_failures](crate::Error::fetch_failures).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchFailure {
+    /// Name of the file, e.g. "rustc-1.70.0-src.tar.xz".
+    pub target_file: String,
+    /// The file has to be downloaded by hand, e.g. because of its license.
+    pub requires_manual_fetching: bool,
+    /// Downloading the file failed.
+    pub failed_automatic_fetching: bool,
+    /// Checksums the file didn't match, e.g. "SHA256", empty if it wasn't checked or matched.
+    pub failed_integrity_checks: String,
+}
+
+/// What a successful [`Action`] produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionOutcome {
+    Done,
+    /// Files a pretend fetch would download, with their size in bytes.
+    WouldFetch(Vec<(PathBuf, u64)>),
+    /// The pretend checks failed, the package should not be installed.
+    PretendFailed,
+}
+
+/// Build fetch failures from groups of 4 strings:
+/// {target file, requires manual fetching "1"/"0", failed automatic fetching "1"/"0", failed integrity checks}.
+pub(crate) fn new_fetch_failures(raw: Vec<String>) -> Vec<FetchFailure> {
+    raw.chunks_exact(4)
+        .map(|f| FetchFailure {
+            target_file: f[0].clone(),
+            requires_manual_fetching: f[1] == "1",
+            failed_automatic_fetching: f[2] == "1",
+            failed_integrity_checks: f[3].clone(),
+        })
+        .collect()
+}
+
+/// Perform an action on a package ID, failures are returned as [`Error::ActionFailedError`](crate::Error::ActionFailedError),
+/// with the files a fetch couldn't get as its [fetch_failures](crate::Error::fetch_failures).
+pub fn perform_action(id: &PackageID, action: &Action) -> Result<ActionOutcome> {
+    let env = &id.env.ptr;
+    let p = &id.ptr;
+
+    match action {
+        Action::Fetch(options) | Action::PretendFetch(options) => {
+            let (oe, summary, log, om) = options.output.kind();
+            let raw = paludis_packageid_fetch(
+                env,
+                p,
+                matches!(action, Action::PretendFetch(_)),
+                options.fetch_parts.into(),
+                options.ignore_unfetched,
+                options.exclude_unmirrorable,
+                options.ignore_not_in_manifest,
+                options.safe_resume,
+                oe,
+                summary,
+                &log,
+                &om,
+            )?;
+
+            if let Action::Fetch(_) = action {
+                return Ok(ActionOutcome::Done);
+            }
+
+            let mut res = Vec::new();
+            let mut files = raw.into_iter();
+            while let (Some(destination), Some(size)) = (files.next(), files.next()) {
+                res.push((PathBuf::from(destination), size.parse().unwrap_or(0)));
+            }
+            Ok(ActionOutcome::WouldFetch(res))
+        }
+        Action::Install(options) | Action::Pretend(options) => {
+            let (oe, summary, log, om) = options.output.kind();
+            let replacing = paludis_package_id_sequence_new();
+            for r in &options.replacing {
+                paludis_package_id_sequence_push_back(&replacing, &r.ptr);
+            }
+
+            let passed = paludis_packageid_install(
+                env,
+                p,
+                matches!(action, Action::Pretend(_)),
+                &options.destination,
+                &replacing,
+                oe,
+                summary,
+                &log,
+                &om,
+            )?;
+            Ok(if passed {
+                ActionOutcome::Done
+            } else {
+                ActionOutcome::PretendFailed
+            })
+        }
+        Action::Uninstall(options) => {
+            let (oe, summary, log, om) = options.output.kind();
+            paludis_packageid_uninstall(env, p, &options.config_protect, oe, summary, &log, &om)?;
+            Ok(ActionOutcome::Done)
+        }
+        Action::Info(options) | Action::Config(options) => {
+            let (oe, summary, log, om) = options.output.kind();
+            paludis_packageid_info_or_config(env, p, action.kind().into(), oe, summary, &log, &om)?;
+            Ok(ActionOutcome::Done)
+        }
+    }
+}
@@ -1,13 +1,596 @@
 use cpp::cpp;
 use cxx::{CxxString, CxxVector, SharedPtr};
-use std::{collections::HashMap, ffi::CString};
+use std::{collections::HashMap, ffi::c_void, ffi::CString};
+
+use super::action::new_fetch_failures;
+use super::error::new_error;
+use super::error::new_paludis_error;
+use super::error::Error;
+use super::logging::log_message;
+use super::output_manager::output_sink_drop;
+use super::output_manager::output_sink_event;
+use super::output_manager::output_sink_message;
+use super::output_manager::output_sink_write;
 
 cpp! {{
+    #include <algorithm>
     #include <iostream>
+    #include <sstream>
     #include <string>
     #include <paludis/paludis.hh>
+    #include <paludis/util/fs_error.hh>
+    #include <paludis/util/make_named_values.hh>
+    #include <paludis/output_managers/file_output_manager.hh>
+    #include <paludis/resolver/resolver.hh>
+    #include <paludis/resolver/resolver_functions.hh>
+    #include <paludis/resolver/resolved.hh>
+    #include <paludis/resolver/resolution.hh>
+    #include <paludis/resolver/resolutions_by_resolvent.hh>
+    #include <paludis/resolver/resolvent.hh>
+    #include <paludis/resolver/decision.hh>
+    #include <paludis/resolver/decisions.hh>
+    #include <paludis/resolver/destination.hh>
+    #include <paludis/resolver/constraint.hh>
+    #include <paludis/resolver/reason.hh>
+    #include <paludis/resolver/required_confirmations.hh>
+    #include <paludis/resolver/job_lists.hh>
+    #include <paludis/resolver/job_list.hh>
+    #include <paludis/resolver/job.hh>
+    #include <paludis/resolver/job_requirements.hh>
+    #include <paludis/resolver/allow_choice_changes_helper.hh>
+    #include <paludis/resolver/allowed_to_remove_helper.hh>
+    #include <paludis/resolver/always_via_binary_helper.hh>
+    #include <paludis/resolver/can_use_helper.hh>
+    #include <paludis/resolver/confirm_helper.hh>
+    #include <paludis/resolver/find_replacing_helper.hh>
+    #include <paludis/resolver/get_constraints_for_dependent_helper.hh>
+    #include <paludis/resolver/get_constraints_for_purge_helper.hh>
+    #include <paludis/resolver/get_constraints_for_via_binary_helper.hh>
+    #include <paludis/resolver/get_destination_types_for_blocker_helper.hh>
+    #include <paludis/resolver/get_destination_types_for_error_helper.hh>
+    #include <paludis/resolver/get_initial_constraints_for_helper.hh>
+    #include <paludis/resolver/get_resolvents_for_helper.hh>
+    #include <paludis/resolver/get_use_existing_nothing_helper.hh>
+    #include <paludis/resolver/interest_in_spec_helper.hh>
+    #include <paludis/resolver/make_destination_filtered_generator_helper.hh>
+    #include <paludis/resolver/make_origin_filtered_generator_helper.hh>
+    #include <paludis/resolver/make_unmaskable_filter_helper.hh>
+    #include <paludis/resolver/order_early_helper.hh>
+    #include <paludis/resolver/prefer_or_avoid_helper.hh>
+    #include <paludis/resolver/promote_binaries_helper.hh>
+    #include <paludis/resolver/remove_hidden_helper.hh>
+    #include <paludis/resolver/remove_if_dependent_helper.hh>
+
+    // Closest paludis base class of an exception, mapped to a paludis_rs::Error variant
+    template <typename T_>
+    static bool paludis_rs_is(const std::exception &e) {
+        return dynamic_cast<const T_ *>(&e) != nullptr;
+    }
+
+    static std::string paludis_rs_exception_kind(const std::exception &e) {
+        if (paludis_rs_is<paludis::NameError>(e))
+            return "NameError";
+        if (paludis_rs_is<paludis::BadVersionSpecError>(e))
+            return "BadVersionSpecError";
+        if (paludis_rs_is<paludis::PackageDepSpecError>(e))
+            return "PackageDepSpecError";
+        if (paludis_rs_is<paludis::GotASetNotAPackageDepSpec>(e))
+            return "GotASetNotAPackageDepSpec";
+        if (paludis_rs_is<paludis::NoSuchPackageError>(e))
+            return "NoSuchPackageError";
+        if (paludis_rs_is<paludis::AmbiguousPackageNameError>(e))
+            return "AmbiguousPackageNameError";
+        if (paludis_rs_is<paludis::NoSuchRepositoryError>(e))
+            return "NoSuchRepositoryError";
+        if (paludis_rs_is<paludis::DidNotGetExactlyOneError>(e))
+            return "DidNotGetExactlyOneError";
+        if (paludis_rs_is<paludis::SyncFailedError>(e))
+            return "SyncFailedError";
+        if (paludis_rs_is<paludis::ConfigurationError>(e))
+            return "ConfigurationError";
+        if (paludis_rs_is<paludis::ActionFailedError>(e))
+            return "ActionFailedError";
+        if (paludis_rs_is<paludis::ActionAbortedError>(e))
+            return "ActionAbortedError";
+        if (paludis_rs_is<paludis::FSError>(e))
+            return "FSError";
+        if (paludis_rs_is<paludis::NotAvailableError>(e))
+            return "NotAvailableError";
+        if (paludis_rs_is<paludis::InternalError>(e))
+            return "InternalError";
+        if (paludis_rs_is<paludis::Exception>(e))
+            return "Exception";
+        return "Other";
+    }
+
+    // Walk a paludis collection from Rust, keeping what owns the iterators alive
+    template <typename Owner_, typename Iterator_>
+    struct PaludisRsCursor {
+        std::shared_ptr<const Owner_> owner;
+        Iterator_ current;
+        Iterator_ end;
+    };
+
+    typedef PaludisRsCursor<paludis::Environment, paludis::Environment::RepositoryConstIterator> PaludisRsRepositoriesCursor;
+    typedef PaludisRsCursor<paludis::CategoryNamePartSet, paludis::CategoryNamePartSet::ConstIterator> PaludisRsCategoryNamesCursor;
+    typedef PaludisRsCursor<paludis::QualifiedPackageNameSet, paludis::QualifiedPackageNameSet::ConstIterator> PaludisRsPackageNamesCursor;
+    typedef PaludisRsCursor<paludis::PackageIDSequence, paludis::PackageIDSequence::ConstIterator> PaludisRsPackageIDsCursor;
+    typedef PaludisRsCursor<paludis::MetadataSectionKey, paludis::MetadataSectionKey::MetadataConstIterator> PaludisRsMetadataKeysCursor;
+    typedef PaludisRsCursor<paludis::Choices, paludis::Choices::ConstIterator> PaludisRsChoicesCursor;
+
+    // The value of a metadata key of a known kind, throws std::bad_cast otherwise
+    template <typename Key_>
+    static const Key_ &paludis_rs_key(const std::shared_ptr<paludis::MetadataKey> &k) {
+        return dynamic_cast<const Key_ &>(*k);
+    }
+
+    template <typename T_>
+    static std::vector<std::string> *paludis_rs_stringify_all(const T_ &value) {
+        std::vector<std::string> *res = new std::vector<std::string>();
+        for (const auto &v : value)
+            res->push_back(stringify(v));
+        return res;
+    }
+
+    static paludis::OutputExclusivity paludis_rs_output_exclusivity(uint8_t oe) {
+        switch (oe) {
+            case 1: return paludis::oe_with_others;
+            case 2: return paludis::oe_exclusive;
+            default: return paludis::oe_background;
+        }
+    }
+
+    static paludis::ClientOutputFeatures paludis_rs_client_output_features(bool summary) {
+        paludis::ClientOutputFeatures cof;
+        if (summary)
+            cof += paludis::cof_summary_at_end;
+        return cof;
+    }
+
+    // Stream buffer handing what is written to a Rust OutputSink
+    class PaludisRsSinkBuf : public std::streambuf {
+        void *sink;
+        bool is_stderr;
+
+        void write(const char *data, size_t len) {
+            rust!(PaludisRsSinkBuf_write [sink : *mut c_void as "void *", is_stderr : bool as "bool", data : *const u8 as "const char *", len : usize as "size_t"] {
+                output_sink_write(sink, is_stderr, data, len)
+            });
+        }
+
+    protected:
+        int_type overflow(int_type c) override {
+            if (c != traits_type::eof()) {
+                char ch(traits_type::to_char_type(c));
+                write(&ch, 1);
+            }
+            return traits_type::not_eof(c);
+        }
+
+        std::streamsize xsputn(const char *data, std::streamsize len) override {
+            write(data, len);
+            return len;
+        }
+
+    public:
+        PaludisRsSinkBuf(void *s, bool e) : sink(s), is_stderr(e) {}
+    };
+
+    // Output manager implemented by a Rust OutputSink, which it owns
+    class PaludisRsOutputManager : public paludis::OutputManager {
+        void *sink;
+        PaludisRsSinkBuf stdout_buf, stderr_buf;
+        std::ostream stdout_os, stderr_os;
+
+        // Events: 0 succeeded, 1 ignore_succeeded, 2 nothing_more_to_come, 3 flush
+        void event(uint8_t e) {
+            rust!(PaludisRsOutputManager_event [sink : *mut c_void as "void *", e : u8 as "uint8_t"] {
+                output_sink_event(sink, e)
+            });
+        }
+
+    public:
+        PaludisRsOutputManager(void *s) :
+            sink(s), stdout_buf(s, false), stderr_buf(s, true), stdout_os(&stdout_buf), stderr_os(&stderr_buf) {}
+
+        ~PaludisRsOutputManager() override {
+            stdout_os.flush();
+            stderr_os.flush();
+            rust!(PaludisRsOutputManager_drop [sink : *mut c_void as "void *"] {
+                output_sink_drop(sink)
+            });
+        }
+
+        std::ostream &stdout_stream() override {
+            return stdout_os;
+        }
+
+        std::ostream &stderr_stream() override {
+            return stderr_os;
+        }
+
+        void message(const paludis::MessageType t, const std::string &s) override {
+            uint8_t kind(0);
+            switch (t) {
+                case paludis::mt_debug: kind = 0; break;
+                case paludis::mt_info: kind = 1; break;
+                case paludis::mt_warn: kind = 2; break;
+                case paludis::mt_error: kind = 3; break;
+                default: kind = 4; break;
+            }
+            const char *data(s.data());
+            size_t len(s.size());
+            rust!(PaludisRsOutputManager_message [sink : *mut c_void as "void *", kind : u8 as "uint8_t", data : *const u8 as "const char *", len : usize as "size_t"] {
+                output_sink_message(sink, kind, data, len)
+            });
+        }
+
+        void succeeded() override {
+            event(0);
+        }
+
+        void ignore_succeeded() override {
+            event(1);
+        }
+
+        void nothing_more_to_come() override {
+            event(2);
+        }
+
+        void flush() override {
+            stdout_os.flush();
+            stderr_os.flush();
+            event(3);
+        }
+
+        bool want_to_flush() const override {
+            return false;
+        }
+    };
+
+    // Stream buffer handing each log message written by paludis to Rust.
+    // Paludis ends each message with std::endl, whose flush delivers it.
+    class PaludisRsLogBuf : public std::streambuf {
+        std::string buffer;
+
+    protected:
+        int_type overflow(int_type c) override {
+            if (c != traits_type::eof())
+                buffer.push_back(traits_type::to_char_type(c));
+            return traits_type::not_eof(c);
+        }
+
+        std::streamsize xsputn(const char *data, std::streamsize len) override {
+            buffer.append(data, len);
+            return len;
+        }
+
+        int sync() override {
+            if (buffer.empty())
+                return 0;
+            std::string message;
+            message.swap(buffer);
+            const char *data(message.data());
+            size_t len(message.size());
+            rust!(PaludisRsLogBuf_deliver [data : *const u8 as "const char *", len : usize as "size_t"] {
+                log_message(data, len)
+            });
+            return 0;
+        }
+    };
+
+    static PaludisRsLogBuf paludis_rs_log_buf;
+    static std::ostream paludis_rs_log_stream(&paludis_rs_log_buf);
+
+    // Create the output manager of an action when paludis asks for it, and keep it to report success.
+    // With a log path, the output goes to "<log>.out" instead of following output.conf.
+    // An output manager given upfront is used as is.
+    struct PaludisRsActionOutputManager {
+        const paludis::Environment *env;
+        std::shared_ptr<const paludis::PackageID> id;
+        paludis::OutputExclusivity oe;
+        paludis::ClientOutputFeatures cof;
+        std::string log;
+        std::shared_ptr<paludis::OutputManager> om;
+
+        std::shared_ptr<paludis::OutputManager> operator()(const paludis::Action &a) {
+            if (!om) {
+                if (log.empty()) {
+                    om = env->create_output_manager(paludis::CreateOutputManagerForPackageIDActionInfo(id, a, oe, cof));
+                } else {
+                    paludis::FSPath path(log);
+                    om = std::make_shared<paludis::FileOutputManager>(path.dirname(), path.basename(), true, false, nullptr, "");
+                }
+            }
+            return om;
+        }
+
+        void succeeded() {
+            if (om)
+                om->succeeded();
+        }
+    };
+
+    static paludis::WantPhase paludis_rs_want_all_phases(const std::string &) {
+        return paludis::wp_yes;
+    }
+
+    static bool paludis_rs_ignore_nothing(const paludis::FSPath &) {
+        return false;
+    }
+
+    static void paludis_rs_perform_uninstall(const std::shared_ptr<const paludis::PackageID> &id, const paludis::UninstallActionOptions &options) {
+        paludis::UninstallAction a(options);
+        id->perform_action(a);
+    }
+
+    static paludis::FetchActionOptions paludis_rs_fetch_action_options(PaludisRsActionOutputManager &om,
+            const std::shared_ptr<paludis::Sequence<paludis::FetchActionFailure>> &errors, uint8_t parts,
+            bool ignore_unfetched, bool exclude_unmirrorable, bool ignore_not_in_manifest, bool safe_resume) {
+        paludis::FetchParts fetch_parts;
+        if (parts & 1)
+            fetch_parts += paludis::fp_regulars;
+        if (parts & 2)
+            fetch_parts += paludis::fp_extras;
+        if (parts & 4)
+            fetch_parts += paludis::fp_unneeded;
+
+        return paludis::make_named_values<paludis::FetchActionOptions>(
+            paludis::n::cross_compile_host() = "",
+            paludis::n::errors() = errors,
+            paludis::n::exclude_unmirrorable() = exclude_unmirrorable,
+            paludis::n::fetch_parts() = fetch_parts,
+            paludis::n::ignore_not_in_manifest() = ignore_not_in_manifest,
+            paludis::n::ignore_unfetched() = ignore_unfetched,
+            paludis::n::make_output_manager() = std::ref(om),
+            paludis::n::safe_resume() = safe_resume,
+            paludis::n::tool_prefix() = "",
+            paludis::n::want_phase() = &paludis_rs_want_all_phases
+        );
+    }
+
+    // Record the {destination, size} of each file a pretend fetch would download
+    class PaludisRsPretendFetchAction : public paludis::PretendFetchAction {
+    public:
+        std::vector<std::string> *res;
+        PaludisRsPretendFetchAction(const paludis::FetchActionOptions &options, std::vector<std::string> *r) :
+            paludis::PretendFetchAction(options), res(r) {}
+
+        void will_fetch(const paludis::FSPath &destination, const unsigned long size_in_bytes) override {
+            res->push_back(stringify(destination));
+            res->push_back(std::to_string(size_in_bytes));
+        }
+    };
+
+    // Result of a resolution, decisions kept in the order they are described to Rust
+    struct PaludisRsResolution {
+        std::shared_ptr<const paludis::resolver::Resolved> resolved;
+        std::vector<std::shared_ptr<const paludis::resolver::Decision>> decisions;
+    };
+
+    static std::string paludis_rs_describe_reason(const paludis::resolver::Reason &r) {
+        using namespace paludis::resolver;
+        if (auto reason = dynamic_cast<const TargetReason *>(&r))
+            return "target " + reason->extra_information();
+        if (auto reason = dynamic_cast<const DependencyReason *>(&r))
+            return "dependency of " + reason->from_id()->canonical_form(paludis::idcf_full);
+        if (auto reason = dynamic_cast<const SetReason *>(&r))
+            return "from set " + paludis::stringify(reason->set_name());
+        if (dynamic_cast<const DependentReason *>(&r))
+            return "dependent upon a removed package";
+        if (dynamic_cast<const WasUsedByReason *>(&r))
+            return "was used by a removed package";
+        if (dynamic_cast<const ViaBinaryReason *>(&r))
+            return "via binary";
+        if (dynamic_cast<const PresetReason *>(&r))
+            return "preset";
+        return "like other destination type";
+    }
+
+    static std::string paludis_rs_describe_confirmation(const paludis::resolver::RequiredConfirmation &c) {
+        using namespace paludis::resolver;
+        if (dynamic_cast<const DowngradeConfirmation *>(&c))
+            return "downgrade";
+        if (dynamic_cast<const NotBestConfirmation *>(&c))
+            return "not-best";
+        if (dynamic_cast<const BreakConfirmation *>(&c))
+            return "break";
+        if (dynamic_cast<const RemoveSystemPackageConfirmation *>(&c))
+            return "remove-system-package";
+        if (dynamic_cast<const MaskedConfirmation *>(&c))
+            return "masked";
+        if (dynamic_cast<const ChangedChoicesConfirmation *>(&c))
+            return "changed-choices";
+        return "uninstall";
+    }
+
+    // Describe a job as {kind, spec, destination repository, replaced specs, required job numbers}
+    static void paludis_rs_describe_job(const paludis::resolver::Job &j, std::vector<std::string> &res) {
+        using namespace paludis::resolver;
+        std::vector<std::string> requirements;
+        std::string kind("3"), spec, destination, replacing;
+
+        if (auto job = dynamic_cast<const PretendJob *>(&j)) {
+            spec = paludis::stringify(job->origin_id_spec());
+            destination = paludis::stringify(job->destination_repository_name());
+        } else if (auto job = dynamic_cast<const FetchJob *>(&j)) {
+            kind = "0";
+            spec = paludis::stringify(job->origin_id_spec());
+            for (const auto &r : *job->requirements())
+                requirements.push_back(std::to_string(r.job_number()));
+        } else if (auto job = dynamic_cast<const InstallJob *>(&j)) {
+            kind = "1";
+            spec = paludis::stringify(job->origin_id_spec());
+            destination = paludis::stringify(job->destination_repository_name());
+            replacing = paludis::join(job->replacing_specs()->begin(), job->replacing_specs()->end(), " ");
+            for (const auto &r : *job->requirements())
+                requirements.push_back(std::to_string(r.job_number()));
+        } else if (auto job = dynamic_cast<const UninstallJob *>(&j)) {
+            kind = "2";
+            spec = paludis::join(job->ids_to_remove_specs()->begin(), job->ids_to_remove_specs()->end(), " ");
+            for (const auto &r : *job->requirements())
+                requirements.push_back(std::to_string(r.job_number()));
+        }
+
+        res.insert(res.end(), {kind, spec, destination, replacing, paludis::join(requirements.begin(), requirements.end(), ",")});
+    }
+
+    // Describe a slot as {raw value, parallel value, match slot, match subslot}
+    static std::vector<std::string> *paludis_rs_describe_slot(const paludis::Slot &slot) {
+        return new std::vector<std::string>({slot.raw_value(), stringify(slot.parallel_value()),
+                stringify(slot.match_values().first), stringify(slot.match_values().second)});
+    }
+
+    // Describe a contents entry as {kind, path, mtime seconds, mtime nanoseconds, md5, symlink target}, see new_contents on the Rust side.
+    // Kinds: 0 file, 1 dir, 2 sym, 3 other, empty fields are unknown.
+    static void paludis_rs_describe_contents_entry(const paludis::ContentsEntry &e, std::vector<std::string> &res) {
+        std::string kind("3"), seconds, nanoseconds, md5, target;
+        if (dynamic_cast<const paludis::ContentsFileEntry *>(&e))
+            kind = "0";
+        else if (dynamic_cast<const paludis::ContentsDirEntry *>(&e))
+            kind = "1";
+        else if (auto sym = dynamic_cast<const paludis::ContentsSymEntry *>(&e)) {
+            kind = "2";
+            target = sym->target_key()->parse_value();
+        }
+
+        auto k(e.find_metadata("mtime"));
+        if (k != e.end_metadata())
+            if (auto key = dynamic_cast<const paludis::MetadataTimeKey *>(k->get())) {
+                paludis::Timestamp t(key->parse_value());
+                seconds = std::to_string(t.seconds());
+                nanoseconds = std::to_string(t.nanoseconds());
+            }
+        k = e.find_metadata("md5");
+        if (k != e.end_metadata())
+            if (auto key = dynamic_cast<const paludis::MetadataValueKey<std::string> *>(k->get()))
+                md5 = key->parse_value();
+
+        res.insert(res.end(), {kind, stringify(e.location_key()->parse_value()), seconds, nanoseconds, md5, target});
+    }
+
+    // Describe a mask as {kind, description, detail, detail}, see new_mask on the Rust side
+    static void paludis_rs_describe_mask(const paludis::Mask &m, std::vector<std::string> &res) {
+        if (auto mask = dynamic_cast<const paludis::UnacceptedMask *>(&m)) {
+            res.insert(res.end(), {"1", mask->description(), mask->unaccepted_key_name(), ""});
+        } else if (auto mask = dynamic_cast<const paludis::RepositoryMask *>(&m)) {
+            res.insert(res.end(), {"2", mask->description(), stringify(mask->mask_file()), mask->comment()});
+        } else if (auto mask = dynamic_cast<const paludis::UnsupportedMask *>(&m)) {
+            res.insert(res.end(), {"3", mask->description(), mask->explanation(), ""});
+        } else {
+            res.insert(res.end(), {"0", m.description(), "", ""});
+        }
+    }
+
+    // Describe a choice value as {unprefixed name, name with prefix, enabled, enabled by default, locked, description, origin, parameter}
+    static void paludis_rs_describe_choice_value(const paludis::ChoiceValue &v, std::vector<std::string> &res) {
+        std::string origin("0");
+        switch (v.origin()) {
+            case paludis::co_explicit: origin = "0"; break;
+            case paludis::co_implicit: origin = "1"; break;
+            case paludis::co_special: origin = "2"; break;
+            default: break;
+        }
+        res.insert(res.end(), {stringify(v.unprefixed_name()), stringify(v.name_with_prefix()),
+                v.enabled() ? "1" : "0", v.enabled_by_default() ? "1" : "0", v.locked() ? "1" : "0",
+                v.description(), origin, v.parameter()});
+    }
+
+    // Kind of a spec tree node, see SpecTree on the Rust side
+    static std::string paludis_rs_spec_kind(const paludis::AllDepSpec &) { return "0"; }
+    static std::string paludis_rs_spec_kind(const paludis::AnyDepSpec &) { return "1"; }
+    static std::string paludis_rs_spec_kind(const paludis::ExactlyOneDepSpec &) { return "2"; }
+    static std::string paludis_rs_spec_kind(const paludis::AtMostOneDepSpec &) { return "3"; }
+    static std::string paludis_rs_spec_kind(const paludis::ConditionalDepSpec &) { return "4"; }
+    static std::string paludis_rs_spec_kind(const paludis::PlainTextLabelDepSpec &) { return "5"; }
+    static std::string paludis_rs_spec_kind(const paludis::URILabelsDepSpec &) { return "5"; }
+    static std::string paludis_rs_spec_kind(const paludis::DepSpec &) { return "6"; }
+
+    // Flatten a spec tree as {kind, text, number of children} triples, children following their parent
+    template <typename Tree_>
+    class PaludisRsSpecTreeFlattener {
+    public:
+        std::vector<std::string> *res;
+        PaludisRsSpecTreeFlattener(std::vector<std::string> *r) : res(r) {}
+
+        template <typename T_>
+        void visit(const paludis::spec_tree_internals::LeafNode<Tree_, T_> &node) {
+            res->push_back(paludis_rs_spec_kind(*node.spec()));
+            res->push_back(stringify(*node.spec()));
+            res->push_back("0");
+        }
+
+        template <typename T_>
+        void visit(const paludis::spec_tree_internals::InnerNode<Tree_, T_> &node) {
+            res->push_back(paludis_rs_spec_kind(*node.spec()));
+            res->push_back(paludis_rs_spec_kind(*node.spec()) == "4" ? stringify(*node.spec()) : "");
+            res->push_back(std::to_string(std::distance(node.begin(), node.end())));
+            for (const auto &child : node)
+                child->accept(*this);
+        }
+    };
+
+    template <typename Tree_>
+    static std::vector<std::string> *paludis_rs_flatten_spec_tree(const paludis::MetadataSpecTreeKey<Tree_> &key) {
+        std::vector<std::string> *res = new std::vector<std::string>();
+        PaludisRsSpecTreeFlattener<Tree_> v(res);
+        key.parse_value()->top()->accept(v);
+        return res;
+    }
+
+    // Contents of a set, flattened as {kind, text, number of children} triples with its package specs apart
+    struct PaludisRsSetContents {
+        std::vector<std::string> flat;
+        std::vector<std::shared_ptr<const paludis::PackageDepSpecData>> packages;
+
+        void visit(const paludis::SetSpecTree::NodeType<paludis::AllDepSpec>::Type &node) {
+            flat.insert(flat.end(), {"0", "", std::to_string(std::distance(node.begin(), node.end()))});
+            for (const auto &child : node)
+                child->accept(*this);
+        }
+
+        void visit(const paludis::SetSpecTree::NodeType<paludis::PackageDepSpec>::Type &node) {
+            flat.insert(flat.end(), {"1", stringify(*node.spec()), "0"});
+            packages.push_back(node.spec()->data());
+        }
+
+        void visit(const paludis::SetSpecTree::NodeType<paludis::NamedSetDepSpec>::Type &node) {
+            flat.insert(flat.end(), {"2", stringify(node.spec()->name()), "0"});
+        }
+    };
+
+    // Describe a caught exception as {kind, class, message, backtrace}, see take_exception
+    static std::vector<std::string> *paludis_rs_exception(const std::exception &e) {
+        const paludis::Exception *pe = dynamic_cast<const paludis::Exception *>(&e);
+        if (pe)
+            return new std::vector<std::string>({paludis_rs_exception_kind(e), pe->what(), pe->message(), pe->backtrace("\n")});
+        return new std::vector<std::string>({paludis_rs_exception_kind(e), "std::exception", e.what(), ""});
+    }
 }}
 
+/// Turn the exception description filled by paludis_rs_exception into an [`Error`].
+/// A null pointer means that nothing was thrown.
+fn take_exception(err: *mut CxxVector<CxxString>) -> Result<(), Error> {
+    if err.is_null() {
+        return Ok(());
+    }
+
+    let temp = unsafe { Box::from_raw(err) };
+    let mut fields = (*temp)
+        .into_iter()
+        .map(|e| e.to_string_lossy().into_owned());
+
+    let kind = fields.next().unwrap_or_default();
+    Err(new_error(
+        &kind,
+        new_paludis_error(
+            fields.next().unwrap_or_default(),
+            fields.next().unwrap_or_default(),
+            fields.next().unwrap_or_default(),
+        ),
+    ))
+}
+
 /// Try to extract the hostname part of a URL.
 /// Returns an empty string if nothing convincing can be found.
 pub fn extract_host_from_url(s: &str) -> String {
@@ -26,25 +609,55 @@ pub fn extract_host_from_url(s: &str) -> String {
     }
 }
 
-// FIXME: if an environment don't exit the program abort
-// maybe returning a null pointer in the catch section and make this func return an option ?
 /// Equivalent of paludis EnvironmentFactory
-pub fn paludis_environment_new(spec: &str) -> SharedPtr<u64> {
+pub fn paludis_environment_new(spec: &str) -> Result<SharedPtr<u64>, Error> {
     let arg = CString::new(spec).unwrap();
     let ptr = arg.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
-    unsafe {
-        cpp!([ptr as "const char *"] -> SharedPtr<u64> as "std::shared_ptr<paludis::Environment>" {
+    let res = unsafe {
+        cpp!([ptr as "const char *", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<paludis::Environment>" {
             try {
               return paludis::EnvironmentFactory::get_instance()->create(ptr);
             } catch (const std::exception &e) {
-              std::cerr << "error: \"" << e.what() << "\"\n";
-              exit(1);
+              err = paludis_rs_exception(e);
+              return nullptr;
             }
         })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+/// Take a string allocated on the C++ side, a null pointer meaning no string.
+fn optional_string(temp: *mut CxxString) -> Option<String> {
+    if temp.is_null() {
+        None
+    } else {
+        let temp = unsafe { Box::from_raw(temp) };
+        Some(String::from(
+            (*temp).to_str().expect("str conversion goes wrong"),
+        ))
     }
 }
 
+/// Take a vector of strings allocated on the C++ side.
+/// Invalid UTF-8 is replaced rather than dropped, callers decode the vector by position.
+fn strings(temp: *mut CxxVector<CxxString>) -> Vec<String> {
+    let temp = unsafe { Box::from_raw(temp) };
+    (*temp)
+        .into_iter()
+        .map(|e| e.to_string_lossy().into_owned())
+        .collect::<Vec<String>>()
+}
+
+/// Take a vector of strings allocated on the C++ side as raw bytes, e.g. for paths.
+fn byte_strings(temp: *mut CxxVector<CxxString>) -> Vec<Vec<u8>> {
+    let temp = unsafe { Box::from_raw(temp) };
+    (*temp).into_iter().map(|e| e.as_bytes().to_vec()).collect()
+}
+
 pub fn paludis_environment_repositories_names(e: &SharedPtr<u64>) -> Vec<String> {
     let temp = unsafe {
         Box::from_raw(
@@ -66,60 +679,136 @@ pub fn paludis_environment_repositories_names(e: &SharedPtr<u64>) -> Vec<String>
         .collect::<Vec<String>>()
 }
 
-pub fn paludis_environment_has_repository_named(e: &SharedPtr<u64>, repo: &str) -> bool {
-    let arg = CString::new(repo).unwrap();
+pub fn paludis_environment_set_names(e: &SharedPtr<u64>) -> Vec<String> {
+    strings(unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            return paludis_rs_stringify_all(*(*e)->set_names());
+        })
+    })
+}
+
+/// Contents of a named set, None if there is no such set
+pub fn paludis_environment_set(
+    e: &SharedPtr<u64>,
+    name: &str,
+) -> Result<Option<SharedPtr<u64>>, Error> {
+    let arg = CString::new(name).unwrap();
     let ptr = arg.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
-    unsafe {
-        cpp!([e as "std::shared_ptr<paludis::Environment>*", ptr as "const char *"] -> bool as "bool" {
-            return (*e)->has_repository_named(paludis::RepositoryName(ptr));
+    let res = unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", ptr as "const char *", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<PaludisRsSetContents>" {
+            try {
+                auto set((*e)->set(paludis::SetName(ptr)));
+                if (!set)
+                    return nullptr;
+                auto res(std::make_shared<PaludisRsSetContents>());
+                set->top()->accept(*res);
+                return res;
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return nullptr;
+            }
         })
+    };
+
+    take_exception(err)?;
+    if res.is_null() {
+        Ok(None)
+    } else {
+        Ok(Some(res))
     }
 }
 
-pub fn paludis_environment_fetch_repository(e: &SharedPtr<u64>, repo: &str) -> SharedPtr<u64> {
-    let arg = CString::new(repo).unwrap();
-    let ptr = arg.as_ptr();
+/// Set contents as {kind, text, number of children} triples: 0 all, 1 package, 2 named set
+pub fn paludis_set_contents_flat(c: &SharedPtr<u64>) -> Vec<String> {
+    strings(unsafe {
+        cpp!([c as "std::shared_ptr<PaludisRsSetContents>*"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            return new std::vector<std::string>((*c)->flat);
+        })
+    })
+}
 
+/// The i-th package spec of a set, in the order of paludis_set_contents_flat
+pub fn paludis_set_contents_package(c: &SharedPtr<u64>, i: u64) -> SharedPtr<u64> {
     unsafe {
-        cpp!([e as "std::shared_ptr<paludis::Environment>*", ptr as "const char *"] -> SharedPtr<u64> as "std::shared_ptr<paludis::Repository>" {
-            return (*e)->fetch_repository(paludis::RepositoryName(ptr));
+        cpp!([c as "std::shared_ptr<PaludisRsSetContents>*", i as "uint64_t"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::PackageDepSpecData>" {
+            return (*c)->packages.at(i);
         })
     }
 }
 
-pub fn paludis_environment_create_sync_output_manager(
+/// Add (or remove) the package of a spec to the world set, returning false if it was already there (or not there).
+pub fn paludis_environment_world_package(
     e: &SharedPtr<u64>,
-    repo: &str,
-    oe: u8,
-    summary: bool,
-) -> Option<SharedPtr<u64>> {
-    let arg = CString::new(repo).unwrap();
+    p: &SharedPtr<u64>,
+    add: bool,
+) -> Result<bool, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", p as "std::shared_ptr<const paludis::PackageDepSpecData>*", add as "bool", mut err as "std::vector<std::string> *"] -> bool as "bool" {
+            try {
+                if (!(*p)->package_ptr())
+                    throw paludis::PackageDepSpecError("'" + (*p)->as_string() + "' doesn't name a single package");
+                return add ? (*e)->add_to_world(*(*p)->package_ptr()) : (*e)->remove_from_world(*(*p)->package_ptr());
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return false;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+/// Add (or remove) a set to the world set, returning false if it was already there (or not there).
+/// Only sets known to the environment can be added, any set name can be removed.
+pub fn paludis_environment_world_set(
+    e: &SharedPtr<u64>,
+    name: &str,
+    add: bool,
+) -> Result<bool, Error> {
+    let arg = CString::new(name).unwrap();
     let ptr = arg.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
     let res = unsafe {
-        cpp!([e as "std::shared_ptr<paludis::Environment>*", ptr as "const char *", oe as "uint8_t", summary as "bool"] -> SharedPtr<u64> as "const std::shared_ptr<paludis::OutputManager>" {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", ptr as "const char *", add as "bool", mut err as "std::vector<std::string> *"] -> bool as "bool" {
             try {
-                auto cof = paludis::ClientOutputFeatures();
-                if (summary) {
-                    cof = cof + paludis::cof_summary_at_end;
-                }
+                paludis::SetName set(ptr);
+                if (!add)
+                    return (*e)->remove_from_world(set);
+                if (!(*e)->set(set))
+                    throw paludis::NameError(ptr, "set name", "no such set");
+                return (*e)->add_to_world(set);
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return false;
+            }
+        })
+    };
 
-                auto out_exclu = paludis::oe_background;
-                if (oe == 1) {
-                    out_exclu = paludis::oe_with_others;
+    take_exception(err)?;
+    Ok(res)
+}
 
-                } else if (oe == 2) {
-                    out_exclu = paludis::oe_exclusive;
-                }
+pub fn paludis_environment_repositories_cursor(e: &SharedPtr<u64>) -> SharedPtr<u64> {
+    unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*"] -> SharedPtr<u64> as "std::shared_ptr<PaludisRsRepositoriesCursor>" {
+            return std::make_shared<PaludisRsRepositoriesCursor>(PaludisRsRepositoriesCursor{
+                *e, (*e)->begin_repositories(), (*e)->end_repositories()});
+        })
+    }
+}
 
-                paludis::CreateOutputManagerForRepositorySyncInfo info(paludis::RepositoryName(ptr),
-                                        out_exclu,
-                                        cof);
-                return (*e)->create_output_manager(info);
-            } catch (...) {
+pub fn paludis_repositories_cursor_next(c: &SharedPtr<u64>) -> Option<SharedPtr<u64>> {
+    let res = unsafe {
+        cpp!([c as "std::shared_ptr<PaludisRsRepositoriesCursor>*"] -> SharedPtr<u64> as "std::shared_ptr<paludis::Repository>" {
+            if ((*c)->current == (*c)->end)
                 return nullptr;
-            }
+            return *((*c)->current++);
         })
     };
 
@@ -130,79 +819,347 @@ pub fn paludis_environment_create_sync_output_manager(
     }
 }
 
-pub fn paludis_repository_name(r: SharedPtr<u64>) -> String {
-    unsafe {
-        let temp = Box::from_raw(
-            cpp!([r as "std::shared_ptr<paludis::Repository>"] -> *mut CxxString as "const std::string *" {
-              return new std::string(r.get()->name().value());
-            }),
-        );
-        String::from((*temp).to_str().expect("str conversion goes wrong"))
-    }
-}
+pub fn paludis_generator_new(
+    e: &SharedPtr<u64>,
+    kind: u8,
+    arg: &str,
+) -> Result<SharedPtr<u64>, Error> {
+    let arg = CString::new(arg).unwrap();
+    let ptr = arg.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
-pub fn paludis_repository_sync(
-    r: SharedPtr<u64>,
-    source: &str,
-    revision: &str,
-    om: SharedPtr<u64>,
-) -> String {
-    let source = CString::new(source).unwrap();
-    let revision = CString::new(revision).unwrap();
+    let res = unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", kind as "uint8_t", ptr as "const char *", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<paludis::Generator>" {
+            try {
+                switch (kind) {
+                    case 1:
+                        return std::make_shared<paludis::Generator>(paludis::generator::Matches(
+                            paludis::parse_user_package_dep_spec(ptr, e->get(), {paludis::updso_allow_wildcards}),
+                            nullptr, {}));
+                    case 2:
+                        return std::make_shared<paludis::Generator>(paludis::generator::InRepository(paludis::RepositoryName(ptr)));
+                    case 3:
+                        return std::make_shared<paludis::Generator>(paludis::generator::Category(paludis::CategoryNamePart(ptr)));
+                    default:
+                        return std::make_shared<paludis::Generator>(paludis::generator::All());
+                }
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return nullptr;
+            }
+        })
+    };
 
-    let source_ptr = source.as_ptr();
-    let revision_ptr = revision.as_ptr();
+    take_exception(err)?;
+    Ok(res)
+}
 
+pub fn paludis_generator_intersection(g: &SharedPtr<u64>, go: &SharedPtr<u64>) -> SharedPtr<u64> {
     unsafe {
-        let temp = Box::from_raw(
-            cpp!([r as "std::shared_ptr<paludis::Repository>", source_ptr as "const char *", revision_ptr as "const char *", om as "std::shared_ptr<paludis::OutputManager>"] -> *mut CxxString as "const std::string *" {
-               std::string res = "";
+        cpp!([g as "std::shared_ptr<paludis::Generator>*", go as "std::shared_ptr<paludis::Generator>*"] -> SharedPtr<u64> as "std::shared_ptr<paludis::Generator>" {
+            return std::make_shared<paludis::Generator>(**g & **go);
+        })
+    }
+}
 
-                try
-                {
-                    if (! r->sync(source_ptr, revision_ptr, om))
-                        res = "skipped";
-                    else
-                        res = "success";
-                }
-                catch (const paludis::SyncFailedError & e)
-                {
-                    res = "sync_error:" + e.message();
-                }
-                catch (...)
-                {
-                    res = "unknown";
+pub fn paludis_filter_new(
+    e: &SharedPtr<u64>,
+    kind: u8,
+    arg: &str,
+) -> Result<SharedPtr<u64>, Error> {
+    let arg = CString::new(arg).unwrap();
+    let ptr = arg.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", kind as "uint8_t", ptr as "const char *", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<paludis::Filter>" {
+            try {
+                switch (kind) {
+                    case 1:
+                        return std::make_shared<paludis::Filter>(paludis::filter::NotMasked());
+                    case 2:
+                        return std::make_shared<paludis::Filter>(paludis::filter::InstalledAtRoot(paludis::FSPath(ptr)));
+                    case 3:
+                        return std::make_shared<paludis::Filter>(paludis::filter::SupportsAction<paludis::InstallAction>());
+                    case 4:
+                        return std::make_shared<paludis::Filter>(paludis::filter::SupportsAction<paludis::UninstallAction>());
+                    case 5:
+                        return std::make_shared<paludis::Filter>(paludis::filter::SupportsAction<paludis::FetchAction>());
+                    case 6:
+                        return std::make_shared<paludis::Filter>(paludis::filter::SupportsAction<paludis::PretendFetchAction>());
+                    case 7:
+                        return std::make_shared<paludis::Filter>(paludis::filter::SupportsAction<paludis::PretendAction>());
+                    case 8:
+                        return std::make_shared<paludis::Filter>(paludis::filter::SupportsAction<paludis::InfoAction>());
+                    case 9:
+                        return std::make_shared<paludis::Filter>(paludis::filter::SupportsAction<paludis::ConfigAction>());
+                    default:
+                        return std::make_shared<paludis::Filter>(paludis::filter::All());
                 }
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return nullptr;
+            }
+        })
+    };
 
-                // om->succeeded();
-                // om->flush();
-                // om->nothing_more_to_come();
-                return new std::string(res);
-            }),
-        );
-        String::from((*temp).to_str().expect("str conversion goes wrong"))
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_filtered_generator_new(g: &SharedPtr<u64>) -> SharedPtr<u64> {
+    unsafe {
+        cpp!([g as "std::shared_ptr<paludis::Generator>*"] -> SharedPtr<u64> as "std::shared_ptr<paludis::FilteredGenerator>" {
+            return std::make_shared<paludis::FilteredGenerator>(**g | paludis::filter::All());
+        })
     }
 }
 
-pub fn paludis_repository_category_names(r: SharedPtr<u64>) -> Vec<String> {
+pub fn paludis_filtered_generator_filter(
+    fg: &SharedPtr<u64>,
+    f: &SharedPtr<u64>,
+) -> SharedPtr<u64> {
     unsafe {
-        let temp: Box<CxxVector<CxxString>> = Box::from_raw(
-            cpp!([r as "std::shared_ptr<paludis::Repository>"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+        cpp!([fg as "std::shared_ptr<paludis::FilteredGenerator>*", f as "std::shared_ptr<paludis::Filter>*"] -> SharedPtr<u64> as "std::shared_ptr<paludis::FilteredGenerator>" {
+            return std::make_shared<paludis::FilteredGenerator>(**fg | **f);
+        })
+    }
+}
+
+/// Run a selection, the resulting IDs are walked with paludis_package_ids_cursor_next
+pub fn paludis_environment_select(
+    e: &SharedPtr<u64>,
+    fg: &SharedPtr<u64>,
+    selection: u8,
+) -> Result<SharedPtr<u64>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", fg as "std::shared_ptr<paludis::FilteredGenerator>*", selection as "uint8_t", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<PaludisRsPackageIDsCursor>" {
+            try {
+                std::shared_ptr<const paludis::PackageIDSequence> c;
+                switch (selection) {
+                    case 1:
+                        c = (**e)[paludis::selection::BestVersionOnly(**fg)];
+                        break;
+                    case 2:
+                        c = (**e)[paludis::selection::BestVersionInEachSlot(**fg)];
+                        break;
+                    case 3:
+                        c = (**e)[paludis::selection::RequireExactlyOne(**fg)];
+                        break;
+                    case 4:
+                        c = (**e)[paludis::selection::SomeArbitraryVersion(**fg)];
+                        break;
+                    default:
+                        c = (**e)[paludis::selection::AllVersionsSorted(**fg)];
+                }
+                return std::make_shared<PaludisRsPackageIDsCursor>(PaludisRsPackageIDsCursor{
+                    c, c->begin(), c->end()});
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return nullptr;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_environment_has_repository_named(e: &SharedPtr<u64>, repo: &str) -> bool {
+    let arg = CString::new(repo).unwrap();
+    let ptr = arg.as_ptr();
+
+    unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", ptr as "const char *"] -> bool as "bool" {
+            try {
+                return (*e)->has_repository_named(paludis::RepositoryName(ptr));
+            } catch (const paludis::NameError &) {
+                return false;
+            }
+        })
+    }
+}
+
+pub fn paludis_environment_fetch_repository(
+    e: &SharedPtr<u64>,
+    repo: &str,
+) -> Result<SharedPtr<u64>, Error> {
+    let arg = CString::new(repo).unwrap();
+    let ptr = arg.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", ptr as "const char *", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<paludis::Repository>" {
+            try {
+                return (*e)->fetch_repository(paludis::RepositoryName(ptr));
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return nullptr;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_environment_create_sync_output_manager(
+    e: &SharedPtr<u64>,
+    repo: &str,
+    oe: u8,
+    summary: bool,
+) -> Result<SharedPtr<u64>, Error> {
+    let arg = CString::new(repo).unwrap();
+    let ptr = arg.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", ptr as "const char *", oe as "uint8_t", summary as "bool", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "const std::shared_ptr<paludis::OutputManager>" {
+            try {
+                auto cof = paludis::ClientOutputFeatures();
+                if (summary) {
+                    cof = cof + paludis::cof_summary_at_end;
+                }
+
+                auto out_exclu = paludis::oe_background;
+                if (oe == 1) {
+                    out_exclu = paludis::oe_with_others;
+
+                } else if (oe == 2) {
+                    out_exclu = paludis::oe_exclusive;
+                }
+
+                paludis::CreateOutputManagerForRepositorySyncInfo info(paludis::RepositoryName(ptr),
+                                        out_exclu,
+                                        cof);
+                return (*e)->create_output_manager(info);
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return nullptr;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+/// Create the output manager of an action on a package ID, action kinds are those of paludis_packageid_supports_action
+pub fn paludis_environment_create_action_output_manager(
+    e: &SharedPtr<u64>,
+    p: &SharedPtr<u64>,
+    kind: u8,
+    oe: u8,
+    summary: bool,
+) -> Result<SharedPtr<u64>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", p as "std::shared_ptr<const paludis::PackageID>*", kind as "uint8_t", oe as "uint8_t", summary as "bool", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<paludis::OutputManager>" {
+            try {
+                static const char *names[] = {"install", "uninstall", "fetch", "pretend_fetch", "pretend", "info", "config"};
+                paludis::CreateOutputManagerForPackageIDActionInfo info(*p, names[kind < 7 ? kind : 5],
+                        paludis_rs_output_exclusivity(oe), paludis_rs_client_output_features(summary));
+                return (*e)->create_output_manager(info);
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return nullptr;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_repository_name(r: SharedPtr<u64>) -> String {
+    unsafe {
+        let temp = Box::from_raw(
+            cpp!([r as "std::shared_ptr<paludis::Repository>"] -> *mut CxxString as "const std::string *" {
+              return new std::string(r.get()->name().value());
+            }),
+        );
+        String::from((*temp).to_str().expect("str conversion goes wrong"))
+    }
+}
+
+/// Returns the installed root of the repository, empty if it isn't an installed repository
+pub fn paludis_repository_installed_root(r: &SharedPtr<u64>) -> Result<Vec<String>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = strings(unsafe {
+        cpp!([r as "std::shared_ptr<paludis::Repository>*", mut err as "std::vector<std::string> *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            std::vector<std::string> *res = new std::vector<std::string>();
+            try {
+                if ((*r)->installed_root_key())
+                    res->push_back(paludis::stringify((*r)->installed_root_key()->parse_value()));
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+            }
+            return res;
+        })
+    });
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_repository_sync(
+    r: SharedPtr<u64>,
+    source: &str,
+    revision: &str,
+    om: SharedPtr<u64>,
+) -> Result<bool, Error> {
+    let source = CString::new(source).unwrap();
+    let revision = CString::new(revision).unwrap();
+
+    let source_ptr = source.as_ptr();
+    let revision_ptr = revision.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([r as "std::shared_ptr<paludis::Repository>", source_ptr as "const char *", revision_ptr as "const char *", om as "std::shared_ptr<paludis::OutputManager>", mut err as "std::vector<std::string> *"] -> bool as "bool" {
+            try {
+                return r->sync(source_ptr, revision_ptr, om);
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return false;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_repository_category_names(r: SharedPtr<u64>) -> Result<Vec<String>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    unsafe {
+        let temp: Box<CxxVector<CxxString>> = Box::from_raw(
+            cpp!([r as "std::shared_ptr<paludis::Repository>", mut err as "std::vector<std::string> *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
                 std::vector<std::string> res = {};
-                for (const auto & category_name  : *(r.get()->category_names(paludis::RepositoryContentMayExcludes({}))))
-                  res.push_back(std::string(stringify(category_name)));
+                try {
+                  for (const auto & category_name  : *(r.get()->category_names(paludis::RepositoryContentMayExcludes({}))))
+                    res.push_back(std::string(stringify(category_name)));
+                } catch (const std::exception &e) {
+                  err = paludis_rs_exception(e);
+                }
                 return new std::vector<std::string>(res);
             }),
         );
+        take_exception(err)?;
 
-        (*temp)
+        Ok((*temp)
             .into_iter()
             .map(|e| match (*e).to_str() {
                 Ok(s) => Some(String::from(s)),
                 Err(_) => None,
             })
             .flatten()
-            .collect::<Vec<String>>()
+            .collect::<Vec<String>>())
     }
 }
 
@@ -260,13 +1217,17 @@ pub fn paludis_repository_metadata_key(r: SharedPtr<u64>, metadata: &str) -> Sha
     }
 }
 
-pub fn paludis_repository_package_names(r: SharedPtr<u64>, category: &str) -> Vec<String> {
+pub fn paludis_repository_package_names(
+    r: SharedPtr<u64>,
+    category: &str,
+) -> Result<Vec<String>, Error> {
     let arg = CString::new(category).unwrap();
     let ptr = arg.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
     unsafe {
         let temp: Box<CxxVector<CxxString>> = Box::from_raw(
-            cpp!([r as "std::shared_ptr<paludis::Repository>", ptr as "const char *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            cpp!([r as "std::shared_ptr<paludis::Repository>", ptr as "const char *", mut err as "std::vector<std::string> *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
                 std::vector<std::string> res = {};
                 try {
                   paludis::CategoryNamePart category(ptr);
@@ -274,85 +1235,152 @@ pub fn paludis_repository_package_names(r: SharedPtr<u64>, category: &str) -> Ve
                       r->package_names(category, {}));
                   for (const auto &p : *c)
                     res.push_back(std::string(stringify(p)));
-                } catch (const std::exception &e) {}
+                } catch (const std::exception &e) {
+                  err = paludis_rs_exception(e);
+                }
 
                 return new std::vector<std::string>(res);
             }),
         );
+        take_exception(err)?;
 
-        (*temp)
+        Ok((*temp)
             .into_iter()
             .map(|e| match (*e).to_str() {
                 Ok(s) => Some(String::from(s)),
                 Err(_) => None,
             })
             .flatten()
-            .collect::<Vec<String>>()
+            .collect::<Vec<String>>())
     }
 }
 
-pub fn paludis_repository_package_ids_canonical_form(
-    r: SharedPtr<u64>,
-    package: &str,
-) -> Vec<String> {
-    let arg = CString::new(package).unwrap();
+pub fn paludis_repository_category_names_cursor(
+    r: &SharedPtr<u64>,
+) -> Result<SharedPtr<u64>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([r as "std::shared_ptr<paludis::Repository>*", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<PaludisRsCategoryNamesCursor>" {
+            try {
+              std::shared_ptr<const paludis::CategoryNamePartSet> c(
+                  (*r)->category_names(paludis::RepositoryContentMayExcludes({})));
+              return std::make_shared<PaludisRsCategoryNamesCursor>(PaludisRsCategoryNamesCursor{
+                  c, c->begin(), c->end()});
+            } catch (const std::exception &e) {
+              err = paludis_rs_exception(e);
+              return nullptr;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_category_names_cursor_next(c: &SharedPtr<u64>) -> Option<String> {
+    unsafe {
+        let temp = cpp!([c as "std::shared_ptr<PaludisRsCategoryNamesCursor>*"] -> *mut CxxString as "std::string *" {
+            if ((*c)->current == (*c)->end)
+                return nullptr;
+            return new std::string(stringify(*((*c)->current++)));
+        });
+
+        if temp.is_null() {
+            None
+        } else {
+            let temp = Box::from_raw(temp);
+            Some(String::from(
+                (*temp).to_str().expect("str conversion goes wrong"),
+            ))
+        }
+    }
+}
+
+pub fn paludis_repository_package_names_cursor(
+    r: &SharedPtr<u64>,
+    category: &str,
+) -> Result<SharedPtr<u64>, Error> {
+    let arg = CString::new(category).unwrap();
     let ptr = arg.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
+    let res = unsafe {
+        cpp!([r as "std::shared_ptr<paludis::Repository>*", ptr as "const char *", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<PaludisRsPackageNamesCursor>" {
+            try {
+              std::shared_ptr<const paludis::QualifiedPackageNameSet> c(
+                  (*r)->package_names(paludis::CategoryNamePart(ptr), {}));
+              return std::make_shared<PaludisRsPackageNamesCursor>(PaludisRsPackageNamesCursor{
+                  c, c->begin(), c->end()});
+            } catch (const std::exception &e) {
+              err = paludis_rs_exception(e);
+              return nullptr;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_package_names_cursor_next(c: &SharedPtr<u64>) -> Option<String> {
     unsafe {
-        let temp: Box<CxxVector<CxxString>> = Box::from_raw(
-            cpp!([r as "std::shared_ptr<paludis::Repository>", ptr as "const char *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
-                std::vector<std::string> res = {};
-                try {
-                  paludis::QualifiedPackageName q(ptr);
-                  std::shared_ptr<const paludis::PackageIDSequence> c(r->package_ids(q, {}));
-                  for (const auto &p : *c)
-                    res.push_back(p.get()->canonical_form(paludis::idcf_full));
-                } catch (const std::exception &e) {}
+        let temp = cpp!([c as "std::shared_ptr<PaludisRsPackageNamesCursor>*"] -> *mut CxxString as "std::string *" {
+            if ((*c)->current == (*c)->end)
+                return nullptr;
+            return new std::string(stringify(*((*c)->current++)));
+        });
 
-                return new std::vector<std::string>(res);
-            }),
-        );
-        (*temp)
-            .into_iter()
-            .map(|e| match (*e).to_str() {
-                Ok(s) => Some(String::from(s)),
-                Err(_) => None,
-            })
-            .flatten()
-            .collect::<Vec<String>>()
+        if temp.is_null() {
+            None
+        } else {
+            let temp = Box::from_raw(temp);
+            Some(String::from(
+                (*temp).to_str().expect("str conversion goes wrong"),
+            ))
+        }
     }
 }
 
-pub fn paludis_repository_package_id_from_canonical_form(
-    r: SharedPtr<u64>,
+pub fn paludis_repository_package_ids_cursor(
+    r: &SharedPtr<u64>,
     package: &str,
-    canonical_form: &str,
-) -> Option<SharedPtr<u64>> {
+) -> Result<SharedPtr<u64>, Error> {
     let arg = CString::new(package).unwrap();
     let ptr = arg.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
-    let arg_can = CString::new(canonical_form).unwrap();
-    let ptr_can = arg_can.as_ptr();
-
-    let res: SharedPtr<u64> = unsafe {
-        cpp!([r as "std::shared_ptr<paludis::Repository>", ptr as "const char *", ptr_can as "const char *"] ->  SharedPtr<u64> as "std::shared_ptr<const paludis::PackageID>"{
+    let res = unsafe {
+        cpp!([r as "std::shared_ptr<paludis::Repository>*", ptr as "const char *", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<PaludisRsPackageIDsCursor>" {
             try {
-              paludis::QualifiedPackageName q(ptr);
-              std::shared_ptr<const paludis::PackageIDSequence> c(r->package_ids(q, {}));
-              for (const auto &p : *c) {
-                if (p.get()->canonical_form(paludis::idcf_full) == ptr_can)
-                  return p;
-              }
+              std::shared_ptr<const paludis::PackageIDSequence> c(
+                  (*r)->package_ids(paludis::QualifiedPackageName(ptr), {}));
+              return std::make_shared<PaludisRsPackageIDsCursor>(PaludisRsPackageIDsCursor{
+                  c, c->begin(), c->end()});
             } catch (const std::exception &e) {
+              err = paludis_rs_exception(e);
+              return nullptr;
             }
-            return nullptr;
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_package_ids_cursor_next(c: &SharedPtr<u64>) -> Option<SharedPtr<u64>> {
+    let res = unsafe {
+        cpp!([c as "std::shared_ptr<PaludisRsPackageIDsCursor>*"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::PackageID>" {
+            if ((*c)->current == (*c)->end)
+                return nullptr;
+            return *((*c)->current++);
         })
     };
 
     if res.is_null() {
-        return None;
+        None
     } else {
-        return Some(res);
+        Some(res)
     }
 }
 
@@ -409,118 +1437,948 @@ pub fn paludis_packageid_metadata_names(p: SharedPtr<u64>) -> Vec<String> {
             }),
         );
 
-        (*temp)
-            .into_iter()
-            .map(|e| match (*e).to_str() {
-                Ok(s) => Some(String::from(s)),
-                Err(_) => None,
-            })
-            .flatten()
-            .collect::<Vec<String>>()
-    }
+        (*temp)
+            .into_iter()
+            .map(|e| match (*e).to_str() {
+                Ok(s) => Some(String::from(s)),
+                Err(_) => None,
+            })
+            .flatten()
+            .collect::<Vec<String>>()
+    }
+}
+
+pub fn paludis_packageid_metadata_key(p: SharedPtr<u64>, metadata: &str) -> SharedPtr<u64> {
+    let arg = CString::new(metadata).unwrap();
+    let ptr = arg.as_ptr();
+
+    unsafe {
+        cpp!([p as "std::shared_ptr<paludis::PackageID>", ptr as "const char *"] -> SharedPtr<u64> as "const std::shared_ptr<const paludis::MetadataKey>" {
+            paludis::Repository::MetadataConstIterator it(p->find_metadata(ptr));
+            return *it;
+        })
+    }
+}
+
+pub fn paludis_packageid_short_description(p: &SharedPtr<u64>) -> Result<String, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let temp = unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageID>*", mut err as "std::vector<std::string> *"] -> *mut CxxString as "const std::string *" {
+            try {
+                return new std::string((*p)->short_description_key()->parse_value());
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return nullptr;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    unsafe {
+        let temp = Box::from_raw(temp);
+        Ok(String::from(
+            (*temp).to_str().expect("str conversion goes wrong"),
+        ))
+    }
+}
+
+pub fn paludis_packageid_repository_name(p: &SharedPtr<u64>) -> String {
+    unsafe {
+        let temp = cpp!([p as "std::shared_ptr<const paludis::PackageID>*"] -> *mut CxxString as "const std::string *" {
+            return new std::string(stringify((*p)->repository_name()));
+        });
+        String::from((*temp).to_str().expect("str conversion goes wrong"))
+    }
+}
+
+/// 0: full, 1: version, 2: no version, 3: no name
+pub fn paludis_packageid_canonical_form(p: &SharedPtr<u64>, form: u8) -> String {
+    unsafe {
+        let temp = Box::from_raw(
+            cpp!([p as "std::shared_ptr<const paludis::PackageID>*", form as "uint8_t"] -> *mut CxxString as "const std::string *" {
+                switch (form) {
+                    case 1: return new std::string((*p)->canonical_form(paludis::idcf_version));
+                    case 2: return new std::string((*p)->canonical_form(paludis::idcf_no_version));
+                    case 3: return new std::string((*p)->canonical_form(paludis::idcf_no_name));
+                    default: return new std::string((*p)->canonical_form(paludis::idcf_full));
+                }
+            }),
+        );
+        String::from((*temp).to_str().expect("str conversion goes wrong"))
+    }
+}
+
+pub fn paludis_packageid_uniquely_identifying_spec(p: &SharedPtr<u64>) -> SharedPtr<u64> {
+    unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageID>*"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::PackageDepSpecData>" {
+            return (*p)->uniquely_identifying_spec().data();
+        })
+    }
+}
+
+/// Slot described as in paludis_rs_describe_slot, empty if the package ID has no slot
+pub fn paludis_packageid_slot(p: &SharedPtr<u64>) -> Result<Vec<String>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = strings(unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageID>*", mut err as "std::vector<std::string> *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            try {
+                if ((*p)->slot_key())
+                    return paludis_rs_describe_slot((*p)->slot_key()->parse_value());
+                return new std::vector<std::string>();
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return new std::vector<std::string>();
+            }
+        })
+    });
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+/// Contents of an installed package as described by paludis_rs_describe_contents_entry, empty if it has none
+pub fn paludis_packageid_contents(p: &SharedPtr<u64>) -> Result<Vec<Vec<u8>>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = byte_strings(unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageID>*", mut err as "std::vector<std::string> *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            std::vector<std::string> *res = new std::vector<std::string>();
+            try {
+                if ((*p)->contents_key())
+                    for (const auto &e : *(*p)->contents_key()->parse_value())
+                        paludis_rs_describe_contents_entry(*e, *res);
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+            }
+            return res;
+        })
+    });
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+/// Returns the seconds and nanoseconds since the epoch the package was installed at, None if unknown
+pub fn paludis_packageid_installed_time(p: &SharedPtr<u64>) -> Result<Option<(i64, u32)>, Error> {
+    let mut has_key = false;
+    let mut nanoseconds: u32 = 0;
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let seconds = unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageID>*", mut has_key as "bool", mut nanoseconds as "uint32_t", mut err as "std::vector<std::string> *"] -> i64 as "int64_t" {
+            try {
+                if (!(*p)->installed_time_key())
+                    return 0;
+                has_key = true;
+                paludis::Timestamp t((*p)->installed_time_key()->parse_value());
+                nanoseconds = t.nanoseconds();
+                return t.seconds();
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return 0;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(if has_key {
+        Some((seconds, nanoseconds))
+    } else {
+        None
+    })
+}
+
+/// Names of the repositories an installed package came from, empty if unknown
+pub fn paludis_packageid_from_repositories(p: &SharedPtr<u64>) -> Result<Vec<String>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = strings(unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageID>*", mut err as "std::vector<std::string> *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            try {
+                if ((*p)->from_repositories_key())
+                    return paludis_rs_stringify_all(*(*p)->from_repositories_key()->parse_value());
+                return new std::vector<std::string>();
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return new std::vector<std::string>();
+            }
+        })
+    });
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+/// Test if the repository of a package has an installed root, i.e. if the package is installed
+pub fn paludis_packageid_is_installed(e: &SharedPtr<u64>, p: &SharedPtr<u64>) -> bool {
+    unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", p as "std::shared_ptr<const paludis::PackageID>*"] -> bool as "bool" {
+            try {
+                return bool((*e)->fetch_repository((*p)->repository_name())->installed_root_key());
+            } catch (const std::exception &) {
+                return false;
+            }
+        })
+    }
+}
+
+pub fn paludis_packageid_masked(p: &SharedPtr<u64>) -> Result<bool, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageID>*", mut err as "std::vector<std::string> *"] -> bool as "bool" {
+            try {
+                return (*p)->masked();
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return false;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+/// Masks described by groups of 4 strings, see paludis_rs_describe_mask
+pub fn paludis_packageid_masks(p: &SharedPtr<u64>) -> Result<Vec<String>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = strings(unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageID>*", mut err as "std::vector<std::string> *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            std::vector<std::string> *res = new std::vector<std::string>();
+            try {
+                for (auto m((*p)->begin_masks()), m_end((*p)->end_masks()); m != m_end; ++m)
+                    paludis_rs_describe_mask(**m, *res);
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+            }
+            return res;
+        })
+    });
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+/// Overridden masks described by a reason followed by the 4 strings of paludis_rs_describe_mask
+pub fn paludis_packageid_overridden_masks(p: &SharedPtr<u64>) -> Result<Vec<String>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = strings(unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageID>*", mut err as "std::vector<std::string> *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            std::vector<std::string> *res = new std::vector<std::string>();
+            try {
+                for (auto m((*p)->begin_overridden_masks()), m_end((*p)->end_overridden_masks()); m != m_end; ++m) {
+                    res->push_back((*m)->override_reason() == paludis::mro_accepted_unstable ? "0" : "1");
+                    paludis_rs_describe_mask(*(*m)->mask(), *res);
+                }
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+            }
+            return res;
+        })
+    });
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+/// Choices of a package ID, empty if it has none
+pub fn paludis_packageid_choices(p: &SharedPtr<u64>) -> Result<SharedPtr<u64>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageID>*", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::Choices>" {
+            try {
+                if ((*p)->choices_key())
+                    return (*p)->choices_key()->parse_value();
+                return std::make_shared<const paludis::Choices>();
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return nullptr;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_choices_cursor(c: &SharedPtr<u64>) -> SharedPtr<u64> {
+    unsafe {
+        cpp!([c as "std::shared_ptr<const paludis::Choices>*"] -> SharedPtr<u64> as "std::shared_ptr<PaludisRsChoicesCursor>" {
+            return std::make_shared<PaludisRsChoicesCursor>(PaludisRsChoicesCursor{
+                *c, (*c)->begin(), (*c)->end()});
+        })
+    }
+}
+
+pub fn paludis_choices_cursor_next(c: &SharedPtr<u64>) -> Option<SharedPtr<u64>> {
+    let res = unsafe {
+        cpp!([c as "std::shared_ptr<PaludisRsChoicesCursor>*"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::Choice>" {
+            if ((*c)->current == (*c)->end)
+                return nullptr;
+            return *((*c)->current++);
+        })
+    };
+
+    if res.is_null() {
+        None
+    } else {
+        Some(res)
+    }
+}
+
+/// Value named with its prefix (e.g. "python_abis:3.11"), described as in paludis_rs_describe_choice_value, empty if not found
+pub fn paludis_choices_find(c: &SharedPtr<u64>, name: &str) -> Result<Vec<String>, Error> {
+    let arg = CString::new(name).unwrap();
+    let ptr = arg.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = strings(unsafe {
+        cpp!([c as "std::shared_ptr<const paludis::Choices>*", ptr as "const char *", mut err as "std::vector<std::string> *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            std::vector<std::string> *res = new std::vector<std::string>();
+            try {
+                auto v((*c)->find_by_name_with_prefix(paludis::ChoiceNameWithPrefix(ptr)));
+                if (v)
+                    paludis_rs_describe_choice_value(*v, *res);
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+            }
+            return res;
+        })
+    });
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+/// 0: raw name, 1: human name, 2: prefix
+pub fn paludis_choice_str(c: &SharedPtr<u64>, part: u8) -> String {
+    unsafe {
+        let temp = Box::from_raw(
+            cpp!([c as "std::shared_ptr<const paludis::Choice>*", part as "uint8_t"] -> *mut CxxString as "const std::string *" {
+                switch (part) {
+                    case 0: return new std::string((*c)->raw_name());
+                    case 1: return new std::string((*c)->human_name());
+                    default: return new std::string(stringify((*c)->prefix()));
+                }
+            }),
+        );
+        String::from((*temp).to_str().expect("str conversion goes wrong"))
+    }
+}
+
+/// 0: hidden, 1: show with no prefix, 2: consider added or changed
+pub fn paludis_choice_flag(c: &SharedPtr<u64>, part: u8) -> bool {
+    unsafe {
+        cpp!([c as "std::shared_ptr<const paludis::Choice>*", part as "uint8_t"] -> bool as "bool" {
+            switch (part) {
+                case 0: return (*c)->hidden();
+                case 1: return (*c)->show_with_no_prefix();
+                default: return (*c)->consider_added_or_changed();
+            }
+        })
+    }
+}
+
+/// Values of a choice, described as in paludis_rs_describe_choice_value
+pub fn paludis_choice_values(c: &SharedPtr<u64>) -> Vec<String> {
+    strings(unsafe {
+        cpp!([c as "std::shared_ptr<const paludis::Choice>*"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            std::vector<std::string> *res = new std::vector<std::string>();
+            for (const auto &v : **c)
+                paludis_rs_describe_choice_value(*v, *res);
+            return res;
+        })
+    })
+}
+
+/// Same kinds as paludis_filter_new minus 3
+pub fn paludis_packageid_supports_action(p: &SharedPtr<u64>, kind: u8) -> bool {
+    unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageID>*", kind as "uint8_t"] -> bool as "bool" {
+            switch (kind) {
+                case 0: return (*p)->supports_action(paludis::SupportsActionTest<paludis::InstallAction>());
+                case 1: return (*p)->supports_action(paludis::SupportsActionTest<paludis::UninstallAction>());
+                case 2: return (*p)->supports_action(paludis::SupportsActionTest<paludis::FetchAction>());
+                case 3: return (*p)->supports_action(paludis::SupportsActionTest<paludis::PretendFetchAction>());
+                case 4: return (*p)->supports_action(paludis::SupportsActionTest<paludis::PretendAction>());
+                case 5: return (*p)->supports_action(paludis::SupportsActionTest<paludis::InfoAction>());
+                case 6: return (*p)->supports_action(paludis::SupportsActionTest<paludis::ConfigAction>());
+                default: return false;
+            }
+        })
+    }
+}
+
+pub fn paludis_package_id_sequence_new() -> SharedPtr<u64> {
+    unsafe {
+        cpp!([] -> SharedPtr<u64> as "std::shared_ptr<paludis::PackageIDSequence>" {
+            return std::make_shared<paludis::PackageIDSequence>();
+        })
+    }
+}
+
+pub fn paludis_package_id_sequence_push_back(s: &SharedPtr<u64>, p: &SharedPtr<u64>) {
+    unsafe {
+        cpp!([s as "std::shared_ptr<paludis::PackageIDSequence>*", p as "std::shared_ptr<const paludis::PackageID>*"] {
+            (*s)->push_back(*p);
+        })
+    }
+}
+
+/// Perform a fetch action, or a pretend fetch action returning the {destination, size} of the files it would download.
+/// Fetch parts are a bitmask: 1 regulars, 2 extras, 4 unneeded.
+/// Files that couldn't be fetched are attached to the exception, see new_fetch_failures.
+#[allow(clippy::too_many_arguments)]
+pub fn paludis_packageid_fetch(
+    e: &SharedPtr<u64>,
+    p: &SharedPtr<u64>,
+    pretend: bool,
+    parts: u8,
+    ignore_unfetched: bool,
+    exclude_unmirrorable: bool,
+    ignore_not_in_manifest: bool,
+    safe_resume: bool,
+    oe: u8,
+    summary: bool,
+    log: &str,
+    om: &SharedPtr<u64>,
+) -> Result<Vec<String>, Error> {
+    let log = CString::new(log).unwrap();
+    let log = log.as_ptr();
+    let mut failed: *mut CxxVector<CxxString> = std::ptr::null_mut();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = strings(unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", p as "std::shared_ptr<const paludis::PackageID>*", pretend as "bool", parts as "uint8_t", ignore_unfetched as "bool", exclude_unmirrorable as "bool", ignore_not_in_manifest as "bool", safe_resume as "bool", oe as "uint8_t", summary as "bool", log as "const char *", om as "std::shared_ptr<paludis::OutputManager>*", mut failed as "std::vector<std::string> *", mut err as "std::vector<std::string> *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            std::vector<std::string> *res = new std::vector<std::string>();
+            auto errors(std::make_shared<paludis::Sequence<paludis::FetchActionFailure>>());
+            try {
+                PaludisRsActionOutputManager om{e->get(), *p, paludis_rs_output_exclusivity(oe), paludis_rs_client_output_features(summary), std::string(log), *om};
+                paludis::FetchActionOptions options(paludis_rs_fetch_action_options(om, errors, parts,
+                            ignore_unfetched, exclude_unmirrorable, ignore_not_in_manifest, safe_resume));
+
+                if (pretend) {
+                    PaludisRsPretendFetchAction a(options, res);
+                    (*p)->perform_action(a);
+                } else {
+                    paludis::FetchAction a(options);
+                    (*p)->perform_action(a);
+                }
+                om.succeeded();
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+            }
+
+            // {target file, requires manual fetching "1"/"0", failed automatic fetching "1"/"0", failed integrity checks}
+            if (!errors->empty()) {
+                failed = new std::vector<std::string>();
+                for (const auto &f : *errors) {
+                    failed->push_back(f.target_file());
+                    failed->push_back(f.requires_manual_fetching() ? "1" : "0");
+                    failed->push_back(f.failed_automatic_fetching() ? "1" : "0");
+                    failed->push_back(f.failed_integrity_checks());
+                }
+            }
+            return res;
+        })
+    });
+
+    let failed = if failed.is_null() {
+        Vec::new()
+    } else {
+        strings(failed)
+    };
+    take_exception(err).map_err(|e| e.with_fetch_failures(new_fetch_failures(failed)))?;
+    Ok(res)
+}
+
+/// Perform an install action, or a pretend action returning false if the pretend checks failed.
+#[allow(clippy::too_many_arguments)]
+pub fn paludis_packageid_install(
+    e: &SharedPtr<u64>,
+    p: &SharedPtr<u64>,
+    pretend: bool,
+    destination: &str,
+    replacing: &SharedPtr<u64>,
+    oe: u8,
+    summary: bool,
+    log: &str,
+    om: &SharedPtr<u64>,
+) -> Result<bool, Error> {
+    let arg = CString::new(destination).unwrap();
+    let ptr = arg.as_ptr();
+    let log = CString::new(log).unwrap();
+    let log = log.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", p as "std::shared_ptr<const paludis::PackageID>*", pretend as "bool", ptr as "const char *", replacing as "std::shared_ptr<paludis::PackageIDSequence>*", oe as "uint8_t", summary as "bool", log as "const char *", om as "std::shared_ptr<paludis::OutputManager>*", mut err as "std::vector<std::string> *"] -> bool as "bool" {
+            try {
+                PaludisRsActionOutputManager om{e->get(), *p, paludis_rs_output_exclusivity(oe), paludis_rs_client_output_features(summary), std::string(log), *om};
+                std::shared_ptr<paludis::Repository> destination((*e)->fetch_repository(paludis::RepositoryName(ptr)));
+
+                if (pretend) {
+                    paludis::PretendAction a(paludis::make_named_values<paludis::PretendActionOptions>(
+                        paludis::n::destination() = destination,
+                        paludis::n::make_output_manager() = std::ref(om),
+                        paludis::n::replacing() = *replacing
+                    ));
+                    (*p)->perform_action(a);
+                    if (a.failed())
+                        return false;
+                } else {
+                    paludis::InstallAction a(paludis::make_named_values<paludis::InstallActionOptions>(
+                        paludis::n::destination() = destination,
+                        paludis::n::make_output_manager() = std::ref(om),
+                        paludis::n::perform_uninstall() = &paludis_rs_perform_uninstall,
+                        paludis::n::replacing() = *replacing,
+                        paludis::n::want_phase() = &paludis_rs_want_all_phases
+                    ));
+                    (*p)->perform_action(a);
+                }
+                om.succeeded();
+                return true;
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return false;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_packageid_uninstall(
+    e: &SharedPtr<u64>,
+    p: &SharedPtr<u64>,
+    config_protect: &str,
+    oe: u8,
+    summary: bool,
+    log: &str,
+    om: &SharedPtr<u64>,
+) -> Result<(), Error> {
+    let arg = CString::new(config_protect).unwrap();
+    let ptr = arg.as_ptr();
+    let log = CString::new(log).unwrap();
+    let log = log.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", p as "std::shared_ptr<const paludis::PackageID>*", ptr as "const char *", oe as "uint8_t", summary as "bool", log as "const char *", om as "std::shared_ptr<paludis::OutputManager>*", mut err as "std::vector<std::string> *"] {
+            try {
+                PaludisRsActionOutputManager om{e->get(), *p, paludis_rs_output_exclusivity(oe), paludis_rs_client_output_features(summary), std::string(log), *om};
+                paludis::UninstallAction a(paludis::make_named_values<paludis::UninstallActionOptions>(
+                    paludis::n::config_protect() = std::string(ptr),
+                    paludis::n::if_for_install_id() = nullptr,
+                    paludis::n::ignore_for_unmerge() = &paludis_rs_ignore_nothing,
+                    paludis::n::is_overwrite() = false,
+                    paludis::n::make_output_manager() = std::ref(om),
+                    paludis::n::override_contents() = nullptr,
+                    paludis::n::want_phase() = &paludis_rs_want_all_phases
+                ));
+                (*p)->perform_action(a);
+                om.succeeded();
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+            }
+        })
+    };
+
+    take_exception(err)
+}
+
+/// Perform an info (5) or config (6) action
+pub fn paludis_packageid_info_or_config(
+    e: &SharedPtr<u64>,
+    p: &SharedPtr<u64>,
+    kind: u8,
+    oe: u8,
+    summary: bool,
+    log: &str,
+    om: &SharedPtr<u64>,
+) -> Result<(), Error> {
+    let log = CString::new(log).unwrap();
+    let log = log.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", p as "std::shared_ptr<const paludis::PackageID>*", kind as "uint8_t", oe as "uint8_t", summary as "bool", log as "const char *", om as "std::shared_ptr<paludis::OutputManager>*", mut err as "std::vector<std::string> *"] {
+            try {
+                PaludisRsActionOutputManager om{e->get(), *p, paludis_rs_output_exclusivity(oe), paludis_rs_client_output_features(summary), std::string(log), *om};
+                if (kind == 6) {
+                    paludis::ConfigAction a(paludis::make_named_values<paludis::ConfigActionOptions>(
+                        paludis::n::make_output_manager() = std::ref(om)
+                    ));
+                    (*p)->perform_action(a);
+                } else {
+                    paludis::InfoAction a(paludis::make_named_values<paludis::InfoActionOptions>(
+                        paludis::n::make_output_manager() = std::ref(om)
+                    ));
+                    (*p)->perform_action(a);
+                }
+                om.succeeded();
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+            }
+        })
+    };
+
+    take_exception(err)
+}
+
+/// Resolve the given targets, specs and sets being separated by new lines.
+/// Slot preferences: 0 best or installed, 1 installed or best, 2 all, 3 best.
+#[allow(clippy::too_many_arguments)]
+pub fn paludis_resolver_resolve(
+    e: &SharedPtr<u64>,
+    specs: &str,
+    sets: &str,
+    complete: bool,
+    everything: bool,
+    target_slots: u8,
+    slots: u8,
+    permit_downgrade: bool,
+    permit_uninstall: bool,
+    make_binaries: bool,
+) -> Result<SharedPtr<u64>, Error> {
+    let specs = CString::new(specs).unwrap();
+    let specs = specs.as_ptr();
+    let sets = CString::new(sets).unwrap();
+    let sets = sets.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", specs as "const char *", sets as "const char *", complete as "bool", everything as "bool", target_slots as "uint8_t", slots as "uint8_t", permit_downgrade as "bool", permit_uninstall as "bool", make_binaries as "bool", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<PaludisRsResolution>" {
+            using namespace paludis::resolver;
+            try {
+                paludis::Environment *env(e->get());
+                paludis::PackageDepSpec all(paludis::parse_user_package_dep_spec("*/*", env, {paludis::updso_allow_wildcards}));
+
+                auto slot_preferences = [](GetResolventsForHelper &helper, uint8_t preference, bool target) {
+                    bool best(preference != 1), installed(preference == 1 || preference == 2), fallback(preference <= 1);
+                    if (target)
+                        helper.set_target_slots(best, installed, fallback);
+                    else
+                        helper.set_slots(best, installed, fallback);
+                };
+
+                AllowChoiceChangesHelper allow_choice_changes_helper(env);
+                AllowedToRemoveHelper allowed_to_remove_helper(env);
+                AlwaysViaBinaryHelper always_via_binary_helper(env);
+                CanUseHelper can_use_helper(env);
+                ConfirmHelper confirm_helper(env);
+                FindReplacingHelper find_replacing_helper(env);
+                GetConstraintsForDependentHelper get_constraints_for_dependent_helper(env);
+                GetConstraintsForPurgeHelper get_constraints_for_purge_helper(env);
+                GetConstraintsForViaBinaryHelper get_constraints_for_via_binary_helper(env);
+                GetDestinationTypesForBlockerHelper get_destination_types_for_blocker_helper(env);
+                GetDestinationTypesForErrorHelper get_destination_types_for_error_helper(env);
+                GetInitialConstraintsForHelper get_initial_constraints_for_helper(env);
+                GetUseExistingNothingHelper get_use_existing_nothing_helper(env);
+                InterestInSpecHelper interest_in_spec_helper(env);
+                MakeDestinationFilteredGeneratorHelper make_destination_filtered_generator_helper(env);
+                MakeOriginFilteredGeneratorHelper make_origin_filtered_generator_helper(env);
+                MakeUnmaskableFilterHelper make_unmaskable_filter_helper(env);
+                OrderEarlyHelper order_early_helper(env);
+                PreferOrAvoidHelper prefer_or_avoid_helper(env);
+                PromoteBinariesHelper promote_binaries_helper(env);
+                RemoveHiddenHelper remove_hidden_helper(env);
+                RemoveIfDependentHelper remove_if_dependent_helper(env);
+                GetResolventsForHelper get_resolvents_for_helper(env, remove_hidden_helper);
+
+                if (everything) {
+                    get_use_existing_nothing_helper.set_use_existing_for_targets(ue_never);
+                    get_use_existing_nothing_helper.set_use_existing_for_set_targets(ue_never);
+                    get_use_existing_nothing_helper.set_use_existing_for_dependencies(ue_never);
+                } else if (complete) {
+                    get_use_existing_nothing_helper.set_use_existing_for_targets(ue_if_same);
+                    get_use_existing_nothing_helper.set_use_existing_for_set_targets(ue_if_same);
+                    get_use_existing_nothing_helper.set_use_existing_for_dependencies(ue_if_same);
+                } else {
+                    get_use_existing_nothing_helper.set_use_existing_for_targets(ue_never);
+                    get_use_existing_nothing_helper.set_use_existing_for_set_targets(ue_if_possible);
+                    get_use_existing_nothing_helper.set_use_existing_for_dependencies(ue_if_possible);
+                }
+
+                if (complete || everything) {
+                    interest_in_spec_helper.set_follow_installed_build_dependencies(true);
+                    interest_in_spec_helper.set_follow_installed_dependencies(true);
+                }
+
+                slot_preferences(get_resolvents_for_helper, target_slots, true);
+                slot_preferences(get_resolvents_for_helper, slots, false);
+
+                if (permit_downgrade)
+                    confirm_helper.add_permit_downgrade_spec(all);
+                if (permit_uninstall) {
+                    confirm_helper.add_permit_uninstall_spec(all);
+                    allowed_to_remove_helper.add_allowed_to_remove_spec(all);
+                }
+
+                if (make_binaries) {
+                    get_resolvents_for_helper.set_target_destination_type(dt_create_binary);
+                    get_destination_types_for_error_helper.set_target_destination_type(dt_create_binary);
+                }
+
+                ResolverFunctions functions(paludis::make_named_values<ResolverFunctions>(
+                    paludis::n::allow_choice_changes_fn() = std::cref(allow_choice_changes_helper),
+                    paludis::n::allowed_to_remove_fn() = std::cref(allowed_to_remove_helper),
+                    paludis::n::always_via_binary_fn() = std::cref(always_via_binary_helper),
+                    paludis::n::can_use_fn() = std::cref(can_use_helper),
+                    paludis::n::confirm_fn() = std::cref(confirm_helper),
+                    paludis::n::find_replacing_fn() = std::cref(find_replacing_helper),
+                    paludis::n::get_constraints_for_dependent_fn() = std::cref(get_constraints_for_dependent_helper),
+                    paludis::n::get_constraints_for_purge_fn() = std::cref(get_constraints_for_purge_helper),
+                    paludis::n::get_constraints_for_via_binary_fn() = std::cref(get_constraints_for_via_binary_helper),
+                    paludis::n::get_destination_types_for_blocker_fn() = std::cref(get_destination_types_for_blocker_helper),
+                    paludis::n::get_destination_types_for_error_fn() = std::cref(get_destination_types_for_error_helper),
+                    paludis::n::get_initial_constraints_for_fn() = std::cref(get_initial_constraints_for_helper),
+                    paludis::n::get_resolvents_for_fn() = std::cref(get_resolvents_for_helper),
+                    paludis::n::get_use_existing_nothing_fn() = std::cref(get_use_existing_nothing_helper),
+                    paludis::n::interest_in_spec_fn() = std::cref(interest_in_spec_helper),
+                    paludis::n::make_destination_filtered_generator_fn() = std::cref(make_destination_filtered_generator_helper),
+                    paludis::n::make_origin_filtered_generator_fn() = std::cref(make_origin_filtered_generator_helper),
+                    paludis::n::make_unmaskable_filter_fn() = std::cref(make_unmaskable_filter_helper),
+                    paludis::n::order_early_fn() = std::cref(order_early_helper),
+                    paludis::n::prefer_or_avoid_fn() = std::cref(prefer_or_avoid_helper),
+                    paludis::n::promote_binaries_fn() = std::cref(promote_binaries_helper),
+                    paludis::n::remove_hidden_fn() = std::cref(remove_hidden_helper),
+                    paludis::n::remove_if_dependent_fn() = std::cref(remove_if_dependent_helper)
+                ));
+
+                Resolver resolver(env, functions);
+                std::string line;
+                std::istringstream specs_lines(specs);
+                while (std::getline(specs_lines, line))
+                    resolver.add_target(paludis::PackageOrBlockDepSpec(paludis::parse_user_package_dep_spec(line, env, {})), line);
+                std::istringstream sets_lines(sets);
+                while (std::getline(sets_lines, line))
+                    resolver.add_target(paludis::SetName(line), line);
+                resolver.resolve();
+
+                // Taken changes and removals first, in the order they must be done
+                auto res(std::make_shared<PaludisRsResolution>(PaludisRsResolution{resolver.resolved(), {}}));
+                for (const auto &d : *res->resolved->taken_change_or_remove_decisions())
+                    res->decisions.push_back(d.first);
+                for (const auto &r : *res->resolved->resolutions_by_resolvent()) {
+                    if (!r->decision() || dynamic_cast<const NothingNoChangeDecision *>(r->decision().get()))
+                        continue;
+                    if (std::find(res->decisions.begin(), res->decisions.end(), r->decision()) == res->decisions.end())
+                        res->decisions.push_back(r->decision());
+                }
+                return res;
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return nullptr;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
 }
 
-pub fn paludis_packageid_metadata_key(p: SharedPtr<u64>, metadata: &str) -> SharedPtr<u64> {
-    let arg = CString::new(metadata).unwrap();
-    let ptr = arg.as_ptr();
+/// Decisions described as {kind, resolvent, taken, position in the taken changes or empty, destination, reasons, required confirmations}.
+/// Kinds: 0 change, 1 keep, 2 remove, 3 break, 4 unable.
+pub fn paludis_resolution_decisions(r: &SharedPtr<u64>) -> Vec<String> {
+    strings(unsafe {
+        cpp!([r as "std::shared_ptr<PaludisRsResolution>*"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            using namespace paludis::resolver;
+            std::vector<std::string> *res = new std::vector<std::string>();
+            unsigned long taken_changes(std::distance((*r)->resolved->taken_change_or_remove_decisions()->begin(),
+                        (*r)->resolved->taken_change_or_remove_decisions()->end()));
+
+            for (unsigned long i(0); i < (*r)->decisions.size(); ++i) {
+                const Decision &d(*(*r)->decisions[i]);
+                std::string kind("4"), destination;
+                std::vector<std::string> reasons, confirmations;
+
+                if (auto decision = dynamic_cast<const ChangesToMakeDecision *>(&d)) {
+                    kind = "0";
+                    if (decision->destination())
+                        destination = paludis::stringify(decision->destination()->repository());
+                } else if (dynamic_cast<const ExistingNoChangeDecision *>(&d)) {
+                    kind = "1";
+                } else if (dynamic_cast<const RemoveDecision *>(&d)) {
+                    kind = "2";
+                } else if (dynamic_cast<const BreakDecision *>(&d)) {
+                    kind = "3";
+                }
 
-    unsafe {
-        cpp!([p as "std::shared_ptr<paludis::PackageID>", ptr as "const char *"] -> SharedPtr<u64> as "const std::shared_ptr<const paludis::MetadataKey>" {
-            paludis::Repository::MetadataConstIterator it(p->find_metadata(ptr));
-            return *it;
+                if (auto decision = dynamic_cast<const ConfirmableDecision *>(&d))
+                    if (decision->required_confirmations_if_any())
+                        for (const auto &c : *decision->required_confirmations_if_any())
+                            confirmations.push_back(paludis_rs_describe_confirmation(*c));
+
+                auto resolution((*r)->resolved->resolutions_by_resolvent()->find(d.resolvent()));
+                if (resolution != (*r)->resolved->resolutions_by_resolvent()->end())
+                    for (const auto &c : *(*resolution)->constraints())
+                        reasons.push_back(paludis_rs_describe_reason(*c->reason()));
+
+                res->insert(res->end(), {kind, paludis::stringify(d.resolvent()), d.taken() ? "1" : "0",
+                        i < taken_changes ? std::to_string(i) : "", destination,
+                        paludis::join(reasons.begin(), reasons.end(), "\n"),
+                        paludis::join(confirmations.begin(), confirmations.end(), "\n")});
+            }
+            return res;
         })
-    }
+    })
 }
 
-pub fn paludis_packageid_short_description(p: &SharedPtr<u64>) -> String {
-    unsafe {
-        let temp = cpp!([p as "std::shared_ptr<const paludis::PackageID>*"] -> *mut CxxString as "const std::string *" {
-            return new std::string((*p)->short_description_key()->parse_value());
-        });
-        String::from((*temp).to_str().expect("str conversion goes wrong"))
+/// The ID a decision installs, keeps or breaks
+pub fn paludis_resolution_decision_id(r: &SharedPtr<u64>, i: u64) -> Option<SharedPtr<u64>> {
+    let res = unsafe {
+        cpp!([r as "std::shared_ptr<PaludisRsResolution>*", i as "uint64_t"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::PackageID>" {
+            using namespace paludis::resolver;
+            const Decision &d(*(*r)->decisions.at(i));
+            if (auto decision = dynamic_cast<const ChangesToMakeDecision *>(&d))
+                return decision->origin_id();
+            if (auto decision = dynamic_cast<const ExistingNoChangeDecision *>(&d))
+                return decision->existing_id();
+            if (auto decision = dynamic_cast<const BreakDecision *>(&d))
+                return decision->existing_id();
+            return nullptr;
+        })
+    };
+
+    if res.is_null() {
+        None
+    } else {
+        Some(res)
     }
 }
 
-pub fn paludis_packageid_repository_name(p: &SharedPtr<u64>) -> String {
+/// Cursor over the IDs a decision replaces or removes, walked with paludis_package_ids_cursor_next
+pub fn paludis_resolution_decision_replacing(r: &SharedPtr<u64>, i: u64) -> SharedPtr<u64> {
     unsafe {
-        let temp = cpp!([p as "std::shared_ptr<const paludis::PackageID>*"] -> *mut CxxString as "const std::string *" {
-            return new std::string(stringify((*p)->repository_name()));
-        });
-        String::from((*temp).to_str().expect("str conversion goes wrong"))
+        cpp!([r as "std::shared_ptr<PaludisRsResolution>*", i as "uint64_t"] -> SharedPtr<u64> as "std::shared_ptr<PaludisRsPackageIDsCursor>" {
+            using namespace paludis::resolver;
+            const Decision &d(*(*r)->decisions.at(i));
+            std::shared_ptr<const paludis::PackageIDSequence> c(std::make_shared<paludis::PackageIDSequence>());
+            if (auto decision = dynamic_cast<const ChangesToMakeDecision *>(&d)) {
+                if (decision->destination())
+                    c = decision->destination()->replacing();
+            } else if (auto decision = dynamic_cast<const RemoveDecision *>(&d)) {
+                c = decision->ids();
+            }
+            return std::make_shared<PaludisRsPackageIDsCursor>(PaludisRsPackageIDsCursor{
+                c, c->begin(), c->end()});
+        })
     }
 }
 
-pub fn paludis_versionspec_is_scm(v: &str) -> bool {
+/// Jobs described as in paludis_rs_describe_job, the pretend jobs or the execute jobs
+pub fn paludis_resolution_jobs(r: &SharedPtr<u64>, pretend: bool) -> Vec<String> {
+    strings(unsafe {
+        cpp!([r as "std::shared_ptr<PaludisRsResolution>*", pretend as "bool"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            std::vector<std::string> *res = new std::vector<std::string>();
+            if (pretend) {
+                for (const auto &j : *(*r)->resolved->job_lists()->pretend_job_list())
+                    paludis_rs_describe_job(*j, *res);
+            } else {
+                for (const auto &j : *(*r)->resolved->job_lists()->execute_job_list())
+                    paludis_rs_describe_job(*j, *res);
+            }
+            return res;
+        })
+    })
+}
+
+pub fn paludis_versionspec_is_scm(v: &str) -> Result<bool, Error> {
     let arg = CString::new(v).unwrap();
     let ptr = arg.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
-    unsafe {
-        cpp!([ptr as "const char *"] -> bool as "bool" {
+    let res = unsafe {
+        cpp!([ptr as "const char *", mut err as "std::vector<std::string> *"] -> bool as "bool" {
             bool res = false;
             try {
               res = paludis::VersionSpec(std::string(ptr), {}).is_scm();
-            } catch (const std::exception &e) {}
+            } catch (const std::exception &e) {
+              err = paludis_rs_exception(e);
+            }
             return res;
         })
-    }
+    };
+
+    take_exception(err)?;
+    Ok(res)
 }
 
-pub fn paludis_versionspec_eq(v: &str, vo: &str) -> bool {
+pub fn paludis_versionspec_eq(v: &str, vo: &str) -> Result<bool, Error> {
     let arg = CString::new(v).unwrap();
     let ptr = arg.as_ptr();
 
     let arg_o = CString::new(vo).unwrap();
     let ptr_o = arg_o.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
-    unsafe {
-        cpp!([ptr as "const char *", ptr_o as "const char *"] -> bool as "bool" {
+    let res = unsafe {
+        cpp!([ptr as "const char *", ptr_o as "const char *", mut err as "std::vector<std::string> *"] -> bool as "bool" {
             bool res = false;
             try {
               res = paludis::VersionSpec(ptr, {}) == paludis::VersionSpec(ptr_o, {});
-            } catch (const std::exception &e) {}
+            } catch (const std::exception &e) {
+              err = paludis_rs_exception(e);
+            }
 
             return res;
         })
-    }
+    };
+
+    take_exception(err)?;
+    Ok(res)
 }
 
-pub fn paludis_versionspec_compare(v: &str, vo: &str) -> i8 {
+pub fn paludis_versionspec_compare(v: &str, vo: &str) -> Result<i8, Error> {
     let arg = CString::new(v).unwrap();
     let ptr = arg.as_ptr();
 
     let arg_o = CString::new(vo).unwrap();
     let ptr_o = arg_o.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
-    unsafe {
-        cpp!([ptr as "const char *", ptr_o as "const char *"] -> i8 as "int8_t" {
+    let res = unsafe {
+        cpp!([ptr as "const char *", ptr_o as "const char *", mut err as "std::vector<std::string> *"] -> i8 as "int8_t" {
             int8_t res = 0;
             try {
               res = paludis::VersionSpec(ptr, {}).compare(paludis::VersionSpec(ptr_o, {}));
-            } catch (const std::exception &e) {}
+            } catch (const std::exception &e) {
+              err = paludis_rs_exception(e);
+            }
 
             return res;
         })
-    }
+    };
+
+    take_exception(err)?;
+    Ok(res)
 }
 
 // Non official function, just a nice "hack"
-pub fn paludis_versionspec_valid(v: &str) -> bool {
+pub fn paludis_versionspec_valid(v: &str) -> Result<(), Error> {
     let arg = CString::new(v).unwrap();
     let ptr = arg.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
     unsafe {
-        cpp!([ptr as "const char *"] -> bool as "bool" {
-            bool res = true;
-
+        cpp!([ptr as "const char *", mut err as "std::vector<std::string> *"] {
             try {
               paludis::VersionSpec a = paludis::VersionSpec(ptr, {});
             } catch (const std::exception &e) {
-              res = false;
+              err = paludis_rs_exception(e);
             }
-
-            return res;
         })
-    }
+    };
+
+    take_exception(err)
 }
 
 pub fn paludis_metadata_human_name(k: SharedPtr<u64>) -> String {
@@ -676,10 +2534,12 @@ pub fn paludis_metadata_value_type(k: SharedPtr<u64>) -> u8 {
     }
 }
 
-pub fn paludis_metadata_value_str(k: SharedPtr<u64>) -> String {
-    unsafe {
+pub fn paludis_metadata_value_str(k: SharedPtr<u64>) -> Result<String, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
         let temp = Box::from_raw(
-            cpp!([k as "std::shared_ptr<paludis::MetadataKey>"] -> *mut CxxString as "const std::string *" {
+            cpp!([k as "std::shared_ptr<paludis::MetadataKey>", mut err as "std::vector<std::string> *"] -> *mut CxxString as "const std::string *" {
                 class MetadataVisitor {
                 private:
                   std::string indent;
@@ -842,19 +2702,28 @@ pub fn paludis_metadata_value_str(k: SharedPtr<u64>) -> String {
                                             paludis::join(res.begin(), res.end(), "\n"));
                     }
                 };
-                MetadataVisitor v = MetadataVisitor();
-                std::string *res = k->accept_returning<std::string *>(v);
-                return res;
+                try {
+                    MetadataVisitor v = MetadataVisitor();
+                    return k->accept_returning<std::string *>(v);
+                } catch (const std::exception &e) {
+                    err = paludis_rs_exception(e);
+                    return new std::string("");
+                }
             }),
         );
         String::from((*temp).to_str().expect("str conversion goes wrong"))
-    }
+    };
+
+    take_exception(err)?;
+    Ok(res)
 }
 
-pub fn paludis_metadata_value_string(k: SharedPtr<u64>) -> String {
-    unsafe {
+pub fn paludis_metadata_value_string(k: SharedPtr<u64>) -> Result<String, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
         let temp = Box::from_raw(
-            cpp!([k as "std::shared_ptr<paludis::MetadataKey>"] -> *mut CxxString as "const std::string *" {
+            cpp!([k as "std::shared_ptr<paludis::MetadataKey>", mut err as "std::vector<std::string> *"] -> *mut CxxString as "const std::string *" {
                 class MetadataVisitor {
                 public:
                   MetadataVisitor() {}
@@ -965,21 +2834,29 @@ pub fn paludis_metadata_value_string(k: SharedPtr<u64>) -> String {
                   }
                 };
 
-                MetadataVisitor v = MetadataVisitor();
-                std::string *res = k->accept_returning<std::string *>(v);
-                return res;
+                try {
+                    MetadataVisitor v = MetadataVisitor();
+                    return k->accept_returning<std::string *>(v);
+                } catch (const std::exception &e) {
+                    err = paludis_rs_exception(e);
+                    return new std::string("");
+                }
             }),
         );
         String::from((*temp).to_str().expect("str conversion goes wrong"))
-    }
+    };
+
+    take_exception(err)?;
+    Ok(res)
 }
 
-pub fn paludis_metadata_value_map(k: SharedPtr<u64>) -> HashMap<String, String> {
+pub fn paludis_metadata_value_map(k: SharedPtr<u64>) -> Result<HashMap<String, String>, Error> {
     let mut res = HashMap::new();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
     unsafe {
         let temp: Box<CxxVector<CxxString>> = Box::from_raw(
-            cpp!([k as "std::shared_ptr<paludis::MetadataKey>"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            cpp!([k as "std::shared_ptr<paludis::MetadataKey>", mut err as "std::vector<std::string> *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
                 class MetadataVisitor {
                 public:
                   MetadataVisitor() {}
@@ -1095,11 +2972,17 @@ pub fn paludis_metadata_value_map(k: SharedPtr<u64>) -> HashMap<String, String>
                   }
                 };
 
-                MetadataVisitor v = MetadataVisitor();
-                std::vector<std::string> res = k->accept_returning<std::vector<std::string>>(v);
+                std::vector<std::string> res = {};
+                try {
+                    MetadataVisitor v = MetadataVisitor();
+                    res = k->accept_returning<std::vector<std::string>>(v);
+                } catch (const std::exception &e) {
+                    err = paludis_rs_exception(e);
+                }
                 return new std::vector<std::string>(res);
             }),
         );
+        take_exception(err)?;
 
         let raw = (*temp)
             .into_iter()
@@ -1116,12 +2999,16 @@ pub fn paludis_metadata_value_map(k: SharedPtr<u64>) -> HashMap<String, String>
         }
     }
 
-    res
+    Ok(res)
 }
 
-pub fn paludis_metadata_value_dependencyspectree(k: SharedPtr<u64>) -> SharedPtr<u64> {
-    unsafe {
-        cpp!([k as "std::shared_ptr<paludis::MetadataKey>"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>" {
+pub fn paludis_metadata_value_dependencyspectree(
+    k: SharedPtr<u64>,
+) -> Result<SharedPtr<u64>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([k as "std::shared_ptr<paludis::MetadataKey>", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>" {
             class MetadataVisitor {
             public:
               MetadataVisitor() {}
@@ -1152,90 +3039,351 @@ pub fn paludis_metadata_value_dependencyspectree(k: SharedPtr<u64>) -> SharedPtr
                 return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
               }
 
-              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> visit(const paludis::MetadataTimeKey &) {
-                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
-              }
+              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> visit(const paludis::MetadataTimeKey &) {
+                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
+              }
+
+              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> visit(
+                  const paludis::MetadataValueKey<std::shared_ptr<const paludis::Choices>>
+                      &) {
+                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
+              }
+
+              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>
+              visit(const paludis::MetadataSpecTreeKey<paludis::PlainTextSpecTree> &) {
+                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
+              }
+
+              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> visit(
+                  const paludis::MetadataSpecTreeKey<paludis::RequiredUseSpecTree> &) {
+                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
+              }
+
+              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>
+              visit(const paludis::MetadataSpecTreeKey<paludis::LicenseSpecTree> &) {
+                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
+              }
+
+              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>
+              visit(const paludis::MetadataSpecTreeKey<paludis::SimpleURISpecTree> &) {
+                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
+              }
+
+              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> visit(
+                  const paludis::MetadataSpecTreeKey<paludis::DependencySpecTree> &key) {
+                  return key.parse_value()->top();
+              }
+
+              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>
+              visit(const paludis::MetadataSpecTreeKey<paludis::FetchableURISpecTree>
+                        &) {
+                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
+              }
+
+              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>
+              visit(const paludis::MetadataCollectionKey<paludis::KeywordNameSet> &) {
+                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
+              }
+
+              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> visit(
+                  const paludis::MetadataCollectionKey<paludis::Set<std::string>> &) {
+                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
+              }
+
+              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> visit(const paludis::MetadataCollectionKey<
+                                 paludis::Map<std::string, std::string>> &) {
+                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
+              }
+
+              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>
+              visit(const paludis::MetadataCollectionKey<paludis::Sequence<std::string>> &) {
+                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
+              }
+
+              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>
+              visit(const paludis::MetadataCollectionKey<paludis::Maintainers> &) {
+                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
+              }
+
+              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>
+              visit(const paludis::MetadataCollectionKey<paludis::FSPathSequence> &) {
+                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
+              }
+
+              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> visit(const paludis::MetadataCollectionKey<paludis::PackageIDSequence> &) {
+                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
+              }
+
+              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> visit(const paludis::MetadataSectionKey &) {
+                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
+              }
+            };
+
+            try {
+                MetadataVisitor v = MetadataVisitor();
+                return k->accept_returning<std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>>(v);
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return nullptr;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+/// Slot described as in paludis_rs_describe_slot
+pub fn paludis_metadata_value_slot(k: SharedPtr<u64>) -> Result<Vec<String>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = strings(unsafe {
+        cpp!([k as "std::shared_ptr<paludis::MetadataKey>", mut err as "std::vector<std::string> *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            try {
+                return paludis_rs_describe_slot(paludis_rs_key<paludis::MetadataValueKey<paludis::Slot>>(k).parse_value());
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return new std::vector<std::string>();
+            }
+        })
+    });
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_metadata_value_long(k: SharedPtr<u64>) -> Result<i64, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([k as "std::shared_ptr<paludis::MetadataKey>", mut err as "std::vector<std::string> *"] -> i64 as "int64_t" {
+            try {
+                return paludis_rs_key<paludis::MetadataValueKey<long>>(k).parse_value();
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return 0;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_metadata_value_bool(k: SharedPtr<u64>) -> Result<bool, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([k as "std::shared_ptr<paludis::MetadataKey>", mut err as "std::vector<std::string> *"] -> bool as "bool" {
+            try {
+                return paludis_rs_key<paludis::MetadataValueKey<bool>>(k).parse_value();
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return false;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_metadata_value_path(k: SharedPtr<u64>) -> Result<String, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        let temp = Box::from_raw(
+            cpp!([k as "std::shared_ptr<paludis::MetadataKey>", mut err as "std::vector<std::string> *"] -> *mut CxxString as "const std::string *" {
+                try {
+                    return new std::string(paludis_rs_key<paludis::MetadataValueKey<paludis::FSPath>>(k).parse_value().as_string());
+                } catch (const std::exception &e) {
+                    err = paludis_rs_exception(e);
+                    return new std::string("");
+                }
+            }),
+        );
+        String::from((*temp).to_str().expect("str conversion goes wrong"))
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_metadata_value_package_id(k: SharedPtr<u64>) -> Result<SharedPtr<u64>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([k as "std::shared_ptr<paludis::MetadataKey>", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::PackageID>" {
+            try {
+                return paludis_rs_key<paludis::MetadataValueKey<std::shared_ptr<const paludis::PackageID>>>(k).parse_value();
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return nullptr;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+/// Returns the seconds and nanoseconds since the epoch
+pub fn paludis_metadata_value_time(k: SharedPtr<u64>) -> Result<(i64, u32), Error> {
+    let mut nanoseconds: u32 = 0;
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
-              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> visit(
-                  const paludis::MetadataValueKey<std::shared_ptr<const paludis::Choices>>
-                      &) {
-                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
-              }
+    let seconds = unsafe {
+        cpp!([k as "std::shared_ptr<paludis::MetadataKey>", mut nanoseconds as "uint32_t", mut err as "std::vector<std::string> *"] -> i64 as "int64_t" {
+            try {
+                paludis::Timestamp t(paludis_rs_key<paludis::MetadataTimeKey>(k).parse_value());
+                nanoseconds = t.nanoseconds();
+                return t.seconds();
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return 0;
+            }
+        })
+    };
 
-              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>
-              visit(const paludis::MetadataSpecTreeKey<paludis::PlainTextSpecTree> &) {
-                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
-              }
+    take_exception(err)?;
+    Ok((seconds, nanoseconds))
+}
 
-              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> visit(
-                  const paludis::MetadataSpecTreeKey<paludis::RequiredUseSpecTree> &) {
-                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
-              }
+pub fn paludis_metadata_value_choices(k: SharedPtr<u64>) -> Result<SharedPtr<u64>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
-              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>
-              visit(const paludis::MetadataSpecTreeKey<paludis::LicenseSpecTree> &) {
-                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
-              }
+    let res = unsafe {
+        cpp!([k as "std::shared_ptr<paludis::MetadataKey>", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::Choices>" {
+            try {
+                return paludis_rs_key<paludis::MetadataValueKey<std::shared_ptr<const paludis::Choices>>>(k).parse_value();
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return nullptr;
+            }
+        })
+    };
 
-              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>
-              visit(const paludis::MetadataSpecTreeKey<paludis::SimpleURISpecTree> &) {
-                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
-              }
+    take_exception(err)?;
+    Ok(res)
+}
 
-              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> visit(
-                  const paludis::MetadataSpecTreeKey<paludis::DependencySpecTree> &key) {
-                  return key.parse_value()->top();
-              }
+/// Flattened non dependency spec tree, see PaludisRsSpecTreeFlattener
+pub fn paludis_metadata_value_spec_tree(k: SharedPtr<u64>) -> Result<Vec<String>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
-              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>
-              visit(const paludis::MetadataSpecTreeKey<paludis::FetchableURISpecTree>
-                        &) {
-                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
-              }
+    let res = strings(unsafe {
+        cpp!([k as "std::shared_ptr<paludis::MetadataKey>", mut err as "std::vector<std::string> *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            try {
+                if (auto key = dynamic_cast<const paludis::MetadataSpecTreeKey<paludis::PlainTextSpecTree> *>(k.get()))
+                    return paludis_rs_flatten_spec_tree(*key);
+                if (auto key = dynamic_cast<const paludis::MetadataSpecTreeKey<paludis::RequiredUseSpecTree> *>(k.get()))
+                    return paludis_rs_flatten_spec_tree(*key);
+                if (auto key = dynamic_cast<const paludis::MetadataSpecTreeKey<paludis::LicenseSpecTree> *>(k.get()))
+                    return paludis_rs_flatten_spec_tree(*key);
+                if (auto key = dynamic_cast<const paludis::MetadataSpecTreeKey<paludis::SimpleURISpecTree> *>(k.get()))
+                    return paludis_rs_flatten_spec_tree(*key);
+                if (auto key = dynamic_cast<const paludis::MetadataSpecTreeKey<paludis::FetchableURISpecTree> *>(k.get()))
+                    return paludis_rs_flatten_spec_tree(*key);
+                throw std::bad_cast();
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return new std::vector<std::string>();
+            }
+        })
+    });
 
-              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>
-              visit(const paludis::MetadataCollectionKey<paludis::KeywordNameSet> &) {
-                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
-              }
+    take_exception(err)?;
+    Ok(res)
+}
 
-              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> visit(
-                  const paludis::MetadataCollectionKey<paludis::Set<std::string>> &) {
-                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
-              }
+/// Stringified values of a keyword, string, maintainer or path collection
+pub fn paludis_metadata_value_strings(k: SharedPtr<u64>) -> Result<Vec<String>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
-              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> visit(const paludis::MetadataCollectionKey<
-                                 paludis::Map<std::string, std::string>> &) {
-                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
-              }
+    let res = strings(unsafe {
+        cpp!([k as "std::shared_ptr<paludis::MetadataKey>", mut err as "std::vector<std::string> *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            try {
+                if (auto key = dynamic_cast<const paludis::MetadataCollectionKey<paludis::KeywordNameSet> *>(k.get()))
+                    return paludis_rs_stringify_all(*key->parse_value());
+                if (auto key = dynamic_cast<const paludis::MetadataCollectionKey<paludis::Set<std::string>> *>(k.get()))
+                    return paludis_rs_stringify_all(*key->parse_value());
+                if (auto key = dynamic_cast<const paludis::MetadataCollectionKey<paludis::Sequence<std::string>> *>(k.get()))
+                    return paludis_rs_stringify_all(*key->parse_value());
+                if (auto key = dynamic_cast<const paludis::MetadataCollectionKey<paludis::Maintainers> *>(k.get()))
+                    return paludis_rs_stringify_all(*key->parse_value());
+                if (auto key = dynamic_cast<const paludis::MetadataCollectionKey<paludis::FSPathSequence> *>(k.get()))
+                    return paludis_rs_stringify_all(*key->parse_value());
+                throw std::bad_cast();
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return new std::vector<std::string>();
+            }
+        })
+    });
 
-              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>
-              visit(const paludis::MetadataCollectionKey<paludis::Sequence<std::string>> &) {
-                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
-              }
+    take_exception(err)?;
+    Ok(res)
+}
 
-              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>
-              visit(const paludis::MetadataCollectionKey<paludis::Maintainers> &) {
-                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
-              }
+/// Cursor over a package ID sequence value, walked with paludis_package_ids_cursor_next
+pub fn paludis_metadata_value_package_ids(k: SharedPtr<u64>) -> Result<SharedPtr<u64>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
-              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>
-              visit(const paludis::MetadataCollectionKey<paludis::FSPathSequence> &) {
-                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
-              }
+    let res = unsafe {
+        cpp!([k as "std::shared_ptr<paludis::MetadataKey>", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<PaludisRsPackageIDsCursor>" {
+            try {
+                std::shared_ptr<const paludis::PackageIDSequence> c(
+                    paludis_rs_key<paludis::MetadataCollectionKey<paludis::PackageIDSequence>>(k).parse_value());
+                return std::make_shared<PaludisRsPackageIDsCursor>(PaludisRsPackageIDsCursor{
+                    c, c->begin(), c->end()});
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return nullptr;
+            }
+        })
+    };
 
-              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> visit(const paludis::MetadataCollectionKey<paludis::PackageIDSequence> &) {
-                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
-              }
+    take_exception(err)?;
+    Ok(res)
+}
 
-              std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> visit(const paludis::MetadataSectionKey &) {
-                return std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>();
-              }
-            };
+/// Cursor over the keys of a section, walked with paludis_metadata_keys_cursor_next
+pub fn paludis_metadata_value_section(k: SharedPtr<u64>) -> Result<SharedPtr<u64>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
 
-            MetadataVisitor v = MetadataVisitor();
-            std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> res = k->accept_returning<std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>>(v);
-            return res;
+    let res = unsafe {
+        cpp!([k as "std::shared_ptr<paludis::MetadataKey>", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<PaludisRsMetadataKeysCursor>" {
+            try {
+                std::shared_ptr<const paludis::MetadataSectionKey> c(
+                    std::dynamic_pointer_cast<const paludis::MetadataSectionKey>(k));
+                if (!c)
+                    throw std::bad_cast();
+                return std::make_shared<PaludisRsMetadataKeysCursor>(PaludisRsMetadataKeysCursor{
+                    c, c->begin_metadata(), c->end_metadata()});
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return nullptr;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_metadata_keys_cursor_next(c: &SharedPtr<u64>) -> Option<SharedPtr<u64>> {
+    let res = unsafe {
+        cpp!([c as "std::shared_ptr<PaludisRsMetadataKeysCursor>*"] -> SharedPtr<u64> as "std::shared_ptr<paludis::MetadataKey>" {
+            if ((*c)->current == (*c)->end)
+                return nullptr;
+            return std::const_pointer_cast<paludis::MetadataKey>(*((*c)->current++));
         })
+    };
+
+    if res.is_null() {
+        None
+    } else {
+        Some(res)
     }
 }
 
@@ -1427,7 +3575,149 @@ pub fn paludis_dependencyspectree_labels_val(k: SharedPtr<u64>, i: u64) -> Share
                 }
 
                 void visit(const paludis::DependencySpecTree::NodeType<
-                           paludis::ConditionalDepSpec>::Type &) {
+                           paludis::ConditionalDepSpec>::Type &) {
+                }
+
+                void
+                visit(const paludis::DependencySpecTree::NodeType<paludis::AnyDepSpec>::Type
+                          &) {
+                }
+
+                void
+                visit(const paludis::DependencySpecTree::NodeType<paludis::AllDepSpec>::Type
+                          &) {
+                }
+              };
+
+            DependencySpecTreeVisitor v = DependencySpecTreeVisitor(i);
+            k->accept(v);
+            return v.res;
+        })
+    }
+}
+
+pub fn paludis_dependencyspectree_package(k: SharedPtr<u64>) -> SharedPtr<u64> {
+    unsafe {
+        cpp!([k as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::PackageDepSpecData>" {
+              class DependencySpecTreeVisitor {
+              public:
+                std::shared_ptr<const paludis::PackageDepSpecData> res = nullptr;
+                DependencySpecTreeVisitor() {}
+
+                void visit(const paludis::DependencySpecTree::NodeType<
+                           paludis::NamedSetDepSpec>::Type &) {
+                }
+
+                void visit(const paludis::DependencySpecTree::NodeType<
+                           paludis::DependenciesLabelsDepSpec>::Type &) {}
+
+                void visit(const paludis::DependencySpecTree::NodeType<
+                           paludis::PackageDepSpec>::Type &node) {
+                    res = node.spec()->data();
+                }
+
+                void visit(
+                    const paludis::DependencySpecTree::NodeType<paludis::BlockDepSpec>::Type
+                        &) {
+                }
+
+                void visit(const paludis::DependencySpecTree::NodeType<
+                           paludis::ConditionalDepSpec>::Type &) {
+                }
+
+                void
+                visit(const paludis::DependencySpecTree::NodeType<paludis::AnyDepSpec>::Type
+                          &) {
+                }
+
+                void
+                visit(const paludis::DependencySpecTree::NodeType<paludis::AllDepSpec>::Type
+                          &) {
+                }
+              };
+
+            DependencySpecTreeVisitor v = DependencySpecTreeVisitor();
+            k->accept(v);
+            return v.res;
+        })
+    }
+}
+
+pub fn paludis_dependencyspectree_conditional_depspecdata(k: SharedPtr<u64>) -> SharedPtr<u64> {
+    unsafe {
+        cpp!([k as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::ConditionalDepSpecData>" {
+              class DependencySpecTreeVisitor {
+              public:
+                std::shared_ptr<const paludis::ConditionalDepSpecData> res = nullptr;
+                DependencySpecTreeVisitor() {}
+
+                void visit(const paludis::DependencySpecTree::NodeType<
+                           paludis::NamedSetDepSpec>::Type &) {
+                }
+
+                void visit(const paludis::DependencySpecTree::NodeType<
+                           paludis::DependenciesLabelsDepSpec>::Type &) {}
+
+                void visit(const paludis::DependencySpecTree::NodeType<
+                           paludis::PackageDepSpec>::Type &) {
+                }
+
+                void visit(
+                    const paludis::DependencySpecTree::NodeType<paludis::BlockDepSpec>::Type
+                        &) {
+                }
+
+                void visit(const paludis::DependencySpecTree::NodeType<
+                           paludis::ConditionalDepSpec>::Type &node) {
+                    res = node.spec()->data();
+                }
+
+                void
+                visit(const paludis::DependencySpecTree::NodeType<paludis::AnyDepSpec>::Type
+                          &) {
+                }
+
+                void
+                visit(const paludis::DependencySpecTree::NodeType<paludis::AllDepSpec>::Type
+                          &) {
+                }
+              };
+
+            DependencySpecTreeVisitor v = DependencySpecTreeVisitor();
+            k->accept(v);
+            return v.res;
+        })
+    }
+}
+
+pub fn paludis_dependencyspectree_conditional_depspec_len(k: SharedPtr<u64>) -> u64 {
+    unsafe {
+        cpp!([k as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>"] -> u64 as "uint64_t" {
+              class DependencySpecTreeVisitor {
+              public:
+                uint64_t res = 0;
+                DependencySpecTreeVisitor() {}
+
+                void visit(const paludis::DependencySpecTree::NodeType<
+                           paludis::NamedSetDepSpec>::Type &) {
+                }
+
+                void visit(const paludis::DependencySpecTree::NodeType<
+                           paludis::DependenciesLabelsDepSpec>::Type &) {}
+
+                void visit(const paludis::DependencySpecTree::NodeType<
+                           paludis::PackageDepSpec>::Type &) {
+                }
+
+                void visit(
+                    const paludis::DependencySpecTree::NodeType<paludis::BlockDepSpec>::Type
+                        &) {
+                }
+
+                void visit(const paludis::DependencySpecTree::NodeType<
+                           paludis::ConditionalDepSpec>::Type &node) {
+                    for (auto n : node)
+                        res++;
                 }
 
                 void
@@ -1441,20 +3731,24 @@ pub fn paludis_dependencyspectree_labels_val(k: SharedPtr<u64>, i: u64) -> Share
                 }
               };
 
-            DependencySpecTreeVisitor v = DependencySpecTreeVisitor(i);
+            DependencySpecTreeVisitor v = DependencySpecTreeVisitor();
             k->accept(v);
             return v.res;
         })
     }
 }
 
-pub fn paludis_dependencyspectree_package(k: SharedPtr<u64>) -> SharedPtr<u64> {
+pub fn paludis_dependencyspectree_conditional_depspec_val(
+    k: SharedPtr<u64>,
+    i: u64,
+) -> SharedPtr<u64> {
     unsafe {
-        cpp!([k as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::PackageDepSpecData>" {
+        cpp!([k as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>", i as "uint64_t"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>" {
               class DependencySpecTreeVisitor {
               public:
-                std::shared_ptr<const paludis::PackageDepSpecData> res = nullptr;
-                DependencySpecTreeVisitor() {}
+                uint64_t val;
+                std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> res = nullptr;
+                DependencySpecTreeVisitor(const uint64_t v) : val(v) {}
 
                 void visit(const paludis::DependencySpecTree::NodeType<
                            paludis::NamedSetDepSpec>::Type &) {
@@ -1464,8 +3758,7 @@ pub fn paludis_dependencyspectree_package(k: SharedPtr<u64>) -> SharedPtr<u64> {
                            paludis::DependenciesLabelsDepSpec>::Type &) {}
 
                 void visit(const paludis::DependencySpecTree::NodeType<
-                           paludis::PackageDepSpec>::Type &node) {
-                    res = node.spec()->data();
+                           paludis::PackageDepSpec>::Type &) {
                 }
 
                 void visit(
@@ -1474,7 +3767,14 @@ pub fn paludis_dependencyspectree_package(k: SharedPtr<u64>) -> SharedPtr<u64> {
                 }
 
                 void visit(const paludis::DependencySpecTree::NodeType<
-                           paludis::ConditionalDepSpec>::Type &) {
+                           paludis::ConditionalDepSpec>::Type &node) {
+                    uint64_t j = 0;
+                    for (auto n : node) {
+                        if (val == j) {
+                            res = n;
+                        }
+                        j++;
+                    }
                 }
 
                 void
@@ -1488,19 +3788,19 @@ pub fn paludis_dependencyspectree_package(k: SharedPtr<u64>) -> SharedPtr<u64> {
                 }
               };
 
-            DependencySpecTreeVisitor v = DependencySpecTreeVisitor();
+            DependencySpecTreeVisitor v = DependencySpecTreeVisitor(i);
             k->accept(v);
             return v.res;
         })
     }
 }
 
-pub fn paludis_dependencyspectree_conditional_depspecdata(k: SharedPtr<u64>) -> SharedPtr<u64> {
+pub fn paludis_dependencyspectree_all_len(k: SharedPtr<u64>) -> u64 {
     unsafe {
-        cpp!([k as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::ConditionalDepSpecData>" {
+        cpp!([k as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>"] -> u64 as "uint64_t" {
               class DependencySpecTreeVisitor {
               public:
-                std::shared_ptr<const paludis::ConditionalDepSpecData> res = nullptr;
+                uint64_t res = 0;
                 DependencySpecTreeVisitor() {}
 
                 void visit(const paludis::DependencySpecTree::NodeType<
@@ -1520,8 +3820,7 @@ pub fn paludis_dependencyspectree_conditional_depspecdata(k: SharedPtr<u64>) ->
                 }
 
                 void visit(const paludis::DependencySpecTree::NodeType<
-                           paludis::ConditionalDepSpec>::Type &node) {
-                    res = node.spec()->data();
+                           paludis::ConditionalDepSpec>::Type &) {
                 }
 
                 void
@@ -1531,7 +3830,10 @@ pub fn paludis_dependencyspectree_conditional_depspecdata(k: SharedPtr<u64>) ->
 
                 void
                 visit(const paludis::DependencySpecTree::NodeType<paludis::AllDepSpec>::Type
-                          &) {
+                          &node) {
+
+                    for (auto n : node)
+                        res++;
                 }
               };
 
@@ -1542,13 +3844,14 @@ pub fn paludis_dependencyspectree_conditional_depspecdata(k: SharedPtr<u64>) ->
     }
 }
 
-pub fn paludis_dependencyspectree_conditional_depspec_len(k: SharedPtr<u64>) -> u64 {
+pub fn paludis_dependencyspectree_all_val(k: SharedPtr<u64>, i: u64) -> SharedPtr<u64> {
     unsafe {
-        cpp!([k as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>"] -> u64 as "uint64_t" {
+        cpp!([k as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>", i as "uint64_t"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>" {
               class DependencySpecTreeVisitor {
               public:
-                uint64_t res = 0;
-                DependencySpecTreeVisitor() {}
+                uint64_t val;
+                std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> res = nullptr;
+                DependencySpecTreeVisitor(const uint64_t v) : val(v) {}
 
                 void visit(const paludis::DependencySpecTree::NodeType<
                            paludis::NamedSetDepSpec>::Type &) {
@@ -1567,9 +3870,7 @@ pub fn paludis_dependencyspectree_conditional_depspec_len(k: SharedPtr<u64>) ->
                 }
 
                 void visit(const paludis::DependencySpecTree::NodeType<
-                           paludis::ConditionalDepSpec>::Type &node) {
-                    for (auto n : node)
-                        res++;
+                           paludis::ConditionalDepSpec>::Type &) {
                 }
 
                 void
@@ -1579,28 +3880,31 @@ pub fn paludis_dependencyspectree_conditional_depspec_len(k: SharedPtr<u64>) ->
 
                 void
                 visit(const paludis::DependencySpecTree::NodeType<paludis::AllDepSpec>::Type
-                          &) {
+                          &node) {
+                    uint64_t j = 0;
+                    for (auto n : node) {
+                        if (val == j) {
+                            res = n;
+                        }
+                        j++;
+                    }
                 }
               };
 
-            DependencySpecTreeVisitor v = DependencySpecTreeVisitor();
+            DependencySpecTreeVisitor v = DependencySpecTreeVisitor(i);
             k->accept(v);
             return v.res;
         })
     }
 }
 
-pub fn paludis_dependencyspectree_conditional_depspec_val(
-    k: SharedPtr<u64>,
-    i: u64,
-) -> SharedPtr<u64> {
+pub fn paludis_dependencyspectree_block_package(k: SharedPtr<u64>) -> SharedPtr<u64> {
     unsafe {
-        cpp!([k as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>", i as "uint64_t"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>" {
+        cpp!([k as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::PackageDepSpecData>" {
               class DependencySpecTreeVisitor {
               public:
-                uint64_t val;
-                std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>> res = nullptr;
-                DependencySpecTreeVisitor(const uint64_t v) : val(v) {}
+                std::shared_ptr<const paludis::PackageDepSpecData> res = nullptr;
+                DependencySpecTreeVisitor() {}
 
                 void visit(const paludis::DependencySpecTree::NodeType<
                            paludis::NamedSetDepSpec>::Type &) {
@@ -1615,18 +3919,12 @@ pub fn paludis_dependencyspectree_conditional_depspec_val(
 
                 void visit(
                     const paludis::DependencySpecTree::NodeType<paludis::BlockDepSpec>::Type
-                        &) {
+                        &node) {
+                    res = node.spec()->blocking().data();
                 }
 
                 void visit(const paludis::DependencySpecTree::NodeType<
-                           paludis::ConditionalDepSpec>::Type &node) {
-                    uint64_t j = 0;
-                    for (auto n : node) {
-                        if (val == j) {
-                            res = n;
-                        }
-                        j++;
-                    }
+                           paludis::ConditionalDepSpec>::Type &) {
                 }
 
                 void
@@ -1640,14 +3938,64 @@ pub fn paludis_dependencyspectree_conditional_depspec_val(
                 }
               };
 
-            DependencySpecTreeVisitor v = DependencySpecTreeVisitor(i);
+            DependencySpecTreeVisitor v = DependencySpecTreeVisitor();
             k->accept(v);
             return v.res;
         })
     }
 }
 
-pub fn paludis_dependencyspectree_all_len(k: SharedPtr<u64>) -> u64 {
+pub fn paludis_dependencyspectree_block_text(k: SharedPtr<u64>) -> String {
+    unsafe {
+        let temp = Box::from_raw(
+            cpp!([k as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>"] -> *mut CxxString as "const std::string *" {
+                  class DependencySpecTreeVisitor {
+                  public:
+                    std::string res = "";
+                    DependencySpecTreeVisitor() {}
+
+                    void visit(const paludis::DependencySpecTree::NodeType<
+                               paludis::NamedSetDepSpec>::Type &) {
+                    }
+
+                    void visit(const paludis::DependencySpecTree::NodeType<
+                               paludis::DependenciesLabelsDepSpec>::Type &) {}
+
+                    void visit(const paludis::DependencySpecTree::NodeType<
+                               paludis::PackageDepSpec>::Type &) {
+                    }
+
+                    void visit(
+                        const paludis::DependencySpecTree::NodeType<paludis::BlockDepSpec>::Type
+                            &node) {
+                        res = node.spec()->text();
+                    }
+
+                    void visit(const paludis::DependencySpecTree::NodeType<
+                               paludis::ConditionalDepSpec>::Type &) {
+                    }
+
+                    void
+                    visit(const paludis::DependencySpecTree::NodeType<paludis::AnyDepSpec>::Type
+                              &) {
+                    }
+
+                    void
+                    visit(const paludis::DependencySpecTree::NodeType<paludis::AllDepSpec>::Type
+                              &) {
+                    }
+                  };
+
+                DependencySpecTreeVisitor v = DependencySpecTreeVisitor();
+                k->accept(v);
+                return new std::string(v.res);
+            }),
+        );
+        String::from((*temp).to_str().expect("str conversion goes wrong"))
+    }
+}
+
+pub fn paludis_dependencyspectree_any_len(k: SharedPtr<u64>) -> u64 {
     unsafe {
         cpp!([k as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>"] -> u64 as "uint64_t" {
               class DependencySpecTreeVisitor {
@@ -1677,15 +4025,14 @@ pub fn paludis_dependencyspectree_all_len(k: SharedPtr<u64>) -> u64 {
 
                 void
                 visit(const paludis::DependencySpecTree::NodeType<paludis::AnyDepSpec>::Type
-                          &) {
+                          &node) {
+                    for (auto n : node)
+                        res++;
                 }
 
                 void
                 visit(const paludis::DependencySpecTree::NodeType<paludis::AllDepSpec>::Type
-                          &node) {
-
-                    for (auto n : node)
-                        res++;
+                          &) {
                 }
               };
 
@@ -1696,7 +4043,7 @@ pub fn paludis_dependencyspectree_all_len(k: SharedPtr<u64>) -> u64 {
     }
 }
 
-pub fn paludis_dependencyspectree_all_val(k: SharedPtr<u64>, i: u64) -> SharedPtr<u64> {
+pub fn paludis_dependencyspectree_any_val(k: SharedPtr<u64>, i: u64) -> SharedPtr<u64> {
     unsafe {
         cpp!([k as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>", i as "uint64_t"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::spec_tree_internals::BasicNode<paludis::DependencySpecTree>>" {
               class DependencySpecTreeVisitor {
@@ -1727,11 +4074,6 @@ pub fn paludis_dependencyspectree_all_val(k: SharedPtr<u64>, i: u64) -> SharedPt
 
                 void
                 visit(const paludis::DependencySpecTree::NodeType<paludis::AnyDepSpec>::Type
-                          &) {
-                }
-
-                void
-                visit(const paludis::DependencySpecTree::NodeType<paludis::AllDepSpec>::Type
                           &node) {
                     uint64_t j = 0;
                     for (auto n : node) {
@@ -1741,6 +4083,11 @@ pub fn paludis_dependencyspectree_all_val(k: SharedPtr<u64>, i: u64) -> SharedPt
                         j++;
                     }
                 }
+
+                void
+                visit(const paludis::DependencySpecTree::NodeType<paludis::AllDepSpec>::Type
+                          &) {
+                }
               };
 
             DependencySpecTreeVisitor v = DependencySpecTreeVisitor(i);
@@ -1750,17 +4097,216 @@ pub fn paludis_dependencyspectree_all_val(k: SharedPtr<u64>, i: u64) -> SharedPt
     }
 }
 
-pub fn paludis_packagedepspecdata_fullname(p: SharedPtr<u64>) -> String {
+/// Equivalent of paludis parse_user_package_dep_spec, options are updso_allow_wildcards, updso_throw_if_set and updso_no_disambiguation
+pub fn paludis_parse_user_package_dep_spec(
+    e: &SharedPtr<u64>,
+    spec: &str,
+    allow_wildcards: bool,
+    throw_if_set: bool,
+    no_disambiguation: bool,
+) -> Result<SharedPtr<u64>, Error> {
+    let arg = CString::new(spec).unwrap();
+    let ptr = arg.as_ptr();
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", ptr as "const char *", allow_wildcards as "bool", throw_if_set as "bool", no_disambiguation as "bool", mut err as "std::vector<std::string> *"] -> SharedPtr<u64> as "std::shared_ptr<const paludis::PackageDepSpecData>" {
+            try {
+                paludis::UserPackageDepSpecOptions options;
+                if (allow_wildcards)
+                    options += paludis::updso_allow_wildcards;
+                if (throw_if_set)
+                    options += paludis::updso_throw_if_set;
+                if (no_disambiguation)
+                    options += paludis::updso_no_disambiguation;
+
+                return paludis::parse_user_package_dep_spec(ptr, e->get(), options).data();
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return nullptr;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_match_package(
+    e: &SharedPtr<u64>,
+    p: &SharedPtr<u64>,
+    id: &SharedPtr<u64>,
+    ignore_additional_requirements: bool,
+    ignore_choice_requirements: bool,
+) -> Result<bool, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = unsafe {
+        cpp!([e as "std::shared_ptr<paludis::Environment>*", p as "std::shared_ptr<const paludis::PackageDepSpecData>*", id as "std::shared_ptr<const paludis::PackageID>*", ignore_additional_requirements as "bool", ignore_choice_requirements as "bool", mut err as "std::vector<std::string> *"] -> bool as "bool" {
+            try {
+                paludis::MatchPackageOptions options;
+                if (ignore_additional_requirements)
+                    options += paludis::mpo_ignore_additional_requirements;
+                if (ignore_choice_requirements)
+                    options += paludis::mpo_ignore_choice_requirements;
+
+                return paludis::match_package(**e, paludis::PackageDepSpec(*p), *id, nullptr, options);
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+                return false;
+            }
+        })
+    };
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+pub fn paludis_packagedepspecdata_as_string(p: &SharedPtr<u64>) -> String {
     unsafe {
         let temp = Box::from_raw(
-            cpp!([p as "std::shared_ptr<const paludis::PackageDepSpecData>"] -> *mut CxxString as "const std::string *" {
-                return new std::string(stringify(*(p->package_ptr())));
+            cpp!([p as "std::shared_ptr<const paludis::PackageDepSpecData>*"] -> *mut CxxString as "const std::string *" {
+                return new std::string((*p)->as_string());
             }),
         );
         String::from((*temp).to_str().expect("str conversion goes wrong"))
     }
 }
 
+/// Name parts of a package dep spec: 0 for the package, 1 for the category name part, 2 for the package name part
+pub fn paludis_packagedepspecdata_name_part(p: &SharedPtr<u64>, part: u8) -> Option<String> {
+    optional_string(unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageDepSpecData>*", part as "uint8_t"] -> *mut CxxString as "std::string *" {
+            if (part == 0 && (*p)->package_ptr())
+                return new std::string(stringify(*(*p)->package_ptr()));
+            if (part == 1 && (*p)->category_name_part_ptr())
+                return new std::string(stringify(*(*p)->category_name_part_ptr()));
+            if (part == 2 && (*p)->package_name_part_ptr())
+                return new std::string(stringify(*(*p)->package_name_part_ptr()));
+            return nullptr;
+        })
+    })
+}
+
+/// Version requirements as {operator, version} pairs, the operator being the index of the paludis VersionOperatorValue in VersionOperator
+/// Version requirements of a package dep spec as {operator, version} pairs, operators numbered as VersionOperator.
+/// Fails on an operator unknown to this crate.
+pub fn paludis_packagedepspecdata_version_requirements(
+    p: &SharedPtr<u64>,
+) -> Result<Vec<String>, Error> {
+    let mut err: *mut CxxVector<CxxString> = std::ptr::null_mut();
+
+    let res = strings(unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageDepSpecData>*", mut err as "std::vector<std::string> *"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            std::vector<std::string> res = {};
+            try {
+                if ((*p)->version_requirements_ptr()) {
+                    for (const auto &r : *(*p)->version_requirements_ptr()) {
+                        uint8_t op = 0;
+                        switch (r.version_operator().value()) {
+                            case paludis::vo_less: op = 0; break;
+                            case paludis::vo_less_equal: op = 1; break;
+                            case paludis::vo_equal: op = 2; break;
+                            case paludis::vo_tilde: op = 3; break;
+                            case paludis::vo_greater: op = 4; break;
+                            case paludis::vo_greater_equal: op = 5; break;
+                            case paludis::vo_nice_equal_star: op = 6; break;
+                            case paludis::vo_stupid_equal_star: op = 7; break;
+                            case paludis::vo_tilde_greater: op = 8; break;
+                            default:
+                                throw paludis::InternalError(PALUDIS_HERE, "Unknown version operator '" + stringify(r.version_operator()) + "'");
+                        }
+                        res.push_back(std::to_string(op));
+                        res.push_back(stringify(r.version_spec()));
+                    }
+                }
+            } catch (const std::exception &e) {
+                err = paludis_rs_exception(e);
+            }
+            return new std::vector<std::string>(res);
+        })
+    });
+
+    take_exception(err)?;
+    Ok(res)
+}
+
+/// Returns true if all the version requirements must match, false if any of them must match
+pub fn paludis_packagedepspecdata_version_requirements_and(p: &SharedPtr<u64>) -> bool {
+    unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageDepSpecData>*"] -> bool as "bool" {
+            return (*p)->version_requirements_mode() != paludis::vr_or;
+        })
+    }
+}
+
+/// Slot requirement of a package dep spec as {kind, slot, subslot or raw requirement}, empty if it has none.
+/// Kinds: 0 ":slot", 1 ":slot/subslot", 2 ":=", 3 ":slot=", 4 ":*", 5 anything else, given raw.
+pub fn paludis_packagedepspecdata_slot_requirement(p: &SharedPtr<u64>) -> Vec<String> {
+    strings(unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageDepSpecData>*"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            auto res = new std::vector<std::string>();
+            auto r((*p)->slot_requirement_ptr());
+            if (!r)
+                return res;
+
+            if (auto e = dynamic_cast<const paludis::SlotExactPartialRequirement *>(r.get()))
+                res->insert(res->end(), {"0", stringify(e->slot()), ""});
+            else if (auto e = dynamic_cast<const paludis::SlotExactFullRequirement *>(r.get()))
+                res->insert(res->end(), {"1", stringify(e->slots().first), stringify(e->slots().second)});
+            else if (dynamic_cast<const paludis::SlotAnyAtAllLockedRequirement *>(r.get()))
+                res->insert(res->end(), {"2", "", ""});
+            else if (auto e = dynamic_cast<const paludis::SlotAnyPartialLockedRequirement *>(r.get()))
+                res->insert(res->end(), {"3", stringify(e->slot()), ""});
+            else if (dynamic_cast<const paludis::SlotAnyUnlockedRequirement *>(r.get()))
+                res->insert(res->end(), {"4", "", ""});
+            else
+                res->insert(res->end(), {"5", "", stringify(*r)});
+            return res;
+        })
+    })
+}
+
+/// Repository parts of a package dep spec: 0 for ::in_repository, 1 for ::from_repository->, 2 for ::installable_to_repository?
+pub fn paludis_packagedepspecdata_repository(p: &SharedPtr<u64>, part: u8) -> Option<String> {
+    optional_string(unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageDepSpecData>*", part as "uint8_t"] -> *mut CxxString as "std::string *" {
+            if (part == 0 && (*p)->in_repository_ptr())
+                return new std::string(stringify(*(*p)->in_repository_ptr()));
+            if (part == 1 && (*p)->from_repository_ptr())
+                return new std::string(stringify(*(*p)->from_repository_ptr()));
+            if (part == 2 && (*p)->installable_to_repository_ptr())
+                return new std::string(stringify((*p)->installable_to_repository_ptr()->repository()));
+            return nullptr;
+        })
+    })
+}
+
+/// Path parts of a package dep spec: 0 for ::/path (installed at), 1 for ::/path? (installable to)
+pub fn paludis_packagedepspecdata_path(p: &SharedPtr<u64>, part: u8) -> Option<String> {
+    optional_string(unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageDepSpecData>*", part as "uint8_t"] -> *mut CxxString as "std::string *" {
+            if (part == 0 && (*p)->installed_at_path_ptr())
+                return new std::string(stringify(*(*p)->installed_at_path_ptr()));
+            if (part == 1 && (*p)->installable_to_path_ptr())
+                return new std::string(stringify((*p)->installable_to_path_ptr()->path()));
+            return nullptr;
+        })
+    })
+}
+
+pub fn paludis_packagedepspecdata_additional_requirements(p: &SharedPtr<u64>) -> Vec<String> {
+    strings(unsafe {
+        cpp!([p as "std::shared_ptr<const paludis::PackageDepSpecData>*"] -> *mut CxxVector<CxxString> as "std::vector<std::string>*" {
+            std::vector<std::string> res = {};
+            if ((*p)->additional_requirements_ptr())
+                for (const auto &r : *(*p)->additional_requirements_ptr())
+                    res.push_back(r->as_raw_string());
+            return new std::vector<std::string>(res);
+        })
+    })
+}
+
 pub fn paludis_dependencieslabel_text(l: SharedPtr<u64>) -> String {
     unsafe {
         let temp = Box::from_raw(
@@ -1799,15 +4345,70 @@ pub fn paludis_output_manager_flush(om: SharedPtr<u64>) {
     };
 }
 
-// pub fn paludis_log_set_log_level() {
-//     unsafe {
-//         cpp!([ptr as "const char *"] -> SharedPtr<u64> as "std::shared_ptr<paludis::Environment>" {
-//             try {
-//               return paludis::EnvironmentFactory::get_instance()->create(ptr);
-//             } catch (const std::exception &e) {
-//               std::cerr << "error: \"" << e.what() << "\"\n";
-//               exit(1);
-//             }
-//         })
-//     }
-// }
+/// Message types: 0 debug, 1 info, 2 warn, 3 error, 4 log
+pub fn paludis_output_manager_message(om: &SharedPtr<u64>, kind: u8, text: &str) {
+    let text = CString::new(text).unwrap();
+    let ptr = text.as_ptr();
+
+    unsafe {
+        cpp!([om as "std::shared_ptr<paludis::OutputManager>*", kind as "uint8_t", ptr as "const char *"] {
+            paludis::MessageType t(paludis::mt_log);
+            switch (kind) {
+                case 0: t = paludis::mt_debug; break;
+                case 1: t = paludis::mt_info; break;
+                case 2: t = paludis::mt_warn; break;
+                case 3: t = paludis::mt_error; break;
+            }
+            (*om)->message(t, ptr);
+        })
+    }
+}
+
+pub fn paludis_output_manager_nothing_more_to_come(om: &SharedPtr<u64>) {
+    unsafe {
+        cpp!([om as "std::shared_ptr<paludis::OutputManager>*"] {
+            (*om)->nothing_more_to_come();
+        })
+    }
+}
+
+pub fn paludis_output_manager_ignore_succeeded(om: &SharedPtr<u64>) {
+    unsafe {
+        cpp!([om as "std::shared_ptr<paludis::OutputManager>*"] {
+            (*om)->ignore_succeeded();
+        })
+    }
+}
+
+/// Output manager forwarding everything to a sink boxed by OutputManager::from_sink, freed with the manager
+pub fn paludis_output_manager_from_sink(sink: *mut c_void) -> SharedPtr<u64> {
+    unsafe {
+        cpp!([sink as "void *"] -> SharedPtr<u64> as "std::shared_ptr<paludis::OutputManager>" {
+            return std::make_shared<PaludisRsOutputManager>(sink);
+        })
+    }
+}
+
+/// Log levels: 0 debug, 1 qa, 2 warning, 3 silent
+pub fn paludis_log_set_log_level(level: u8) {
+    unsafe {
+        cpp!([level as "uint8_t"] {
+            paludis::LogLevel l(paludis::ll_silent);
+            switch (level) {
+                case 0: l = paludis::ll_debug; break;
+                case 1: l = paludis::ll_qa; break;
+                case 2: l = paludis::ll_warning; break;
+            }
+            paludis::Log::get_instance()->set_log_level(l);
+        })
+    }
+}
+
+/// Send the log messages to logging::log_message, or back to stderr
+pub fn paludis_log_forward(forward: bool) {
+    unsafe {
+        cpp!([forward as "bool"] {
+            paludis::Log::get_instance()->set_log_stream(forward ? &paludis_rs_log_stream : &std::cerr);
+        })
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt::Debug;
+
+use cxx::SharedPtr;
+
+use super::bindings::paludis_choice_flag;
+use super::bindings::paludis_choice_str;
+use super::bindings::paludis_choice_values;
+use super::bindings::paludis_choices_cursor;
+use super::bindings::paludis_choices_cursor_next;
+use super::bindings::paludis_choices_find;
+
+use super::Environment;
+use super::Result;
+
+/// The choices (USE flags, options, ...) of a [`PackageID`](crate::PackageID), grouped in [`Choice`]s.
+///
+/// Choices are borrowed from the [`Environment`] their [`PackageID`](crate::PackageID) comes from, and can't outlive it.
+pub struct Choices<'env> {
+    env: &'env Environment,
+    ptr: SharedPtr<u64>,
+}
+
+pub fn new_choices(env: &Environment, ptr: SharedPtr<u64>) -> Choices<'_> {
+    Choices { env, ptr }
+}
+
+impl<'env> Debug for Choices<'env> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'env> Choices<'env> {
+    /// Iterate over the choice groups, e.g. "build_options" or "python_abis".
+    pub fn iter(&self) -> ChoicesIter<'env> {
+        ChoicesIter {
+            env: self.env,
+            ptr: paludis_choices_cursor(&self.ptr),
+        }
+    }
+
+    /// Find a value by its name with prefix, e.g. "python_abis:3.11" or "doc".
+    pub fn find(&self, name_with_prefix: &str) -> Result<Option<ChoiceValue>> {
+        Ok(new_choice_values(paludis_choices_find(&self.ptr, name_with_prefix)?).pop())
+    }
+}
+
+impl<'env> IntoIterator for &Choices<'env> {
+    type Item = Choice<'env>;
+    type IntoIter = ChoicesIter<'env>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the [`Choice`]s of [`Choices`].
+pub struct ChoicesIter<'env> {
+    env: &'env Environment,
+    ptr: SharedPtr<u64>,
+}
+
+impl<'env> Iterator for ChoicesIter<'env> {
+    type Item = Choice<'env>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        paludis_choices_cursor_next(&self.ptr).map(|ptr| Choice { env: self.env, ptr })
+    }
+}
+
+/// A group of choice values sharing a prefix, e.g. "python_abis".
+pub struct Choice<'env> {
+    env: &'env Environment,
+    ptr: SharedPtr<u64>,
+}
+
+impl<'env> Debug for Choice<'env> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Choice")
+            .field("prefix", &self.prefix())
+            .field("values", &self.values())
+            .finish()
+    }
+}
+
+impl<'env> Choice<'env> {
+    /// Raw name, e.g. "PYTHON_ABIS".
+    pub fn raw_name(&self) -> String {
+        paludis_choice_str(&self.ptr, 0)
+    }
+
+    /// Name to show to a human, e.g. "python_abis".
+    pub fn human_name(&self) -> String {
+        paludis_choice_str(&self.ptr, 1)
+    }
+
+    /// Prefix of the values, e.g. "python_abis", empty for plain USE flags.
+    pub fn prefix(&self) -> String {
+        paludis_choice_str(&self.ptr, 2)
+    }
+
+    /// Should the choice be hidden from the user?
+    pub fn hidden(&self) -> bool {
+        paludis_choice_flag(&self.ptr, 0)
+    }
+
+    /// Should the values be shown without their prefix?
+    pub fn show_with_no_prefix(&self) -> bool {
+        paludis_choice_flag(&self.ptr, 1)
+    }
+
+    /// Should changes to the values be considered when deciding to reinstall?
+    pub fn consider_added_or_changed(&self) -> bool {
+        paludis_choice_flag(&self.ptr, 2)
+    }
+
+    /// Values of the choice.
+    pub fn values(&self) -> Vec<ChoiceValue> {
+        new_choice_values(paludis_choice_values(&self.ptr))
+    }
+}
+
+/// Where a [`ChoiceValue`] comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChoiceOrigin {
+    /// Declared by the package, e.g. in IUSE or MYOPTIONS.
+    Explicit,
+    /// Not declared but available, e.g. USE_EXPAND values.
+    Implicit,
+    /// Added by paludis, e.g. build_options.
+    Special,
+}
+
+/// A value of a [`Choice`], e.g. "doc" or "python_abis:3.11".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChoiceValue {
+    /// Name without prefix, e.g. "3.11".
+    pub unprefixed_name: String,
+    /// Name with prefix, e.g. "python_abis:3.11".
+    pub name_with_prefix: String,
+    pub enabled: bool,
+    pub enabled_by_default: bool,
+    /// The value can't be changed by the user.
+    pub locked: bool,
+    pub description: String,
+    pub origin: ChoiceOrigin,
+    /// Parameter of the value, e.g. the "4" of "jobs=4", empty if it takes none.
+    pub parameter: String,
+}
+
+/// Build choice values from groups of 8 strings.
+fn new_choice_values(raw: Vec<String>) -> Vec<ChoiceValue> {
+    let mut res = Vec::new();
+    let mut values = raw.into_iter();
+    while let (
+        Some(unprefixed_name),
+        Some(name_with_prefix),
+        Some(enabled),
+        Some(enabled_by_default),
+        Some(locked),
+        Some(description),
+        Some(origin),
+        Some(parameter),
+    ) = (
+        values.next(),
+        values.next(),
+        values.next(),
+        values.next(),
+        values.next(),
+        values.next(),
+        values.next(),
+        values.next(),
+    ) {
+        res.push(ChoiceValue {
+            unprefixed_name,
+            name_with_prefix,
+            enabled: enabled == "1",
+            enabled_by_default: enabled_by_default == "1",
+            locked: locked == "1",
+            description,
+            origin: match origin.as_str() {
+                "1" => ChoiceOrigin::Implicit,
+                "2" => ChoiceOrigin::Special,
+                _ => ChoiceOrigin::Explicit,
+            },
+            parameter,
+        });
+    }
+    res
+}
@@ -0,0 +1,104 @@
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStringExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+/// An entry of the contents of an installed [`PackageID`](crate::PackageID), see [contents](crate::PackageID::contents).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ContentsEntry {
+    File {
+        path: PathBuf,
+        /// Modification time recorded at install time.
+        mtime: Option<SystemTime>,
+        /// MD5 checksum recorded at install time.
+        md5: Option<String>,
+    },
+    Dir {
+        path: PathBuf,
+        mtime: Option<SystemTime>,
+    },
+    Sym {
+        path: PathBuf,
+        /// Where the link points, e.g. "python3.11".
+        target: PathBuf,
+        mtime: Option<SystemTime>,
+    },
+    /// Anything else, e.g. a fifo or a device.
+    Other {
+        path: PathBuf,
+        mtime: Option<SystemTime>,
+    },
+}
+
+impl ContentsEntry {
+    /// Where the entry is installed, e.g. "/usr/bin/python3".
+    pub fn path(&self) -> &Path {
+        match self {
+            ContentsEntry::File { path, .. }
+            | ContentsEntry::Dir { path, .. }
+            | ContentsEntry::Sym { path, .. }
+            | ContentsEntry::Other { path, .. } => path,
+        }
+    }
+
+    /// Modification time recorded at install time, None if unknown.
+    pub fn mtime(&self) -> Option<SystemTime> {
+        match self {
+            ContentsEntry::File { mtime, .. }
+            | ContentsEntry::Dir { mtime, .. }
+            | ContentsEntry::Sym { mtime, .. }
+            | ContentsEntry::Other { mtime, .. } => *mtime,
+        }
+    }
+}
+
+/// Build a time from seconds and nanoseconds since the epoch, as paludis timestamps are.
+pub(crate) fn new_system_time(seconds: i64, nanoseconds: u32) -> SystemTime {
+    let since_epoch = Duration::new(seconds.unsigned_abs(), 0);
+    let time = if seconds >= 0 {
+        SystemTime::UNIX_EPOCH + since_epoch
+    } else {
+        SystemTime::UNIX_EPOCH - since_epoch
+    };
+    time + Duration::from_nanos(nanoseconds.into())
+}
+
+/// Build contents entries from groups of 6 byte strings: {kind, path, mtime seconds, mtime nanoseconds, md5, symlink target}.
+/// Paths are kept as raw bytes, they aren't necessarily valid UTF-8.
+pub fn new_contents(raw: Vec<Vec<u8>>) -> Vec<ContentsEntry> {
+    let mut res = Vec::new();
+    let mut entries = raw.into_iter();
+    while let (Some(kind), Some(path), Some(seconds), Some(nanoseconds), Some(md5), Some(target)) = (
+        entries.next(),
+        entries.next(),
+        entries.next(),
+        entries.next(),
+        entries.next(),
+        entries.next(),
+    ) {
+        let text = |bytes: Vec<u8>| String::from_utf8_lossy(&bytes).into_owned();
+        let path = PathBuf::from(OsString::from_vec(path));
+        let mtime = text(seconds)
+            .parse::<i64>()
+            .ok()
+            .map(|s| new_system_time(s, text(nanoseconds).parse().unwrap_or(0)));
+
+        res.push(match text(kind).as_str() {
+            "0" => ContentsEntry::File {
+                path,
+                mtime,
+                md5: Some(text(md5)).filter(|m| !m.is_empty()),
+            },
+            "1" => ContentsEntry::Dir { path, mtime },
+            "2" => ContentsEntry::Sym {
+                path,
+                target: PathBuf::from(OsString::from_vec(target)),
+                mtime,
+            },
+            _ => ContentsEntry::Other { path, mtime },
+        });
+    }
+    res
+}
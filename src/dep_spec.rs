@@ -1,5 +1,7 @@
 use cxx::SharedPtr;
 use std::fmt::Debug;
+use std::fmt::Display;
+use std::path::PathBuf;
 
 use crate::bindings::paludis_dependencyspectree_conditional_depspec_val;
 
@@ -7,6 +9,10 @@ use super::bindings::paludis_conditional_depspecdata_as_string;
 use super::bindings::paludis_dependencieslabel_text;
 use super::bindings::paludis_dependencyspectree_all_len;
 use super::bindings::paludis_dependencyspectree_all_val;
+use super::bindings::paludis_dependencyspectree_any_len;
+use super::bindings::paludis_dependencyspectree_any_val;
+use super::bindings::paludis_dependencyspectree_block_package;
+use super::bindings::paludis_dependencyspectree_block_text;
 use super::bindings::paludis_dependencyspectree_conditional_depspec_len;
 use super::bindings::paludis_dependencyspectree_conditional_depspecdata;
 use super::bindings::paludis_dependencyspectree_labels_len;
@@ -14,15 +20,40 @@ use super::bindings::paludis_dependencyspectree_labels_val;
 use super::bindings::paludis_dependencyspectree_namedset;
 use super::bindings::paludis_dependencyspectree_package;
 use super::bindings::paludis_dependencyspectree_type;
-use super::bindings::paludis_packagedepspecdata_fullname;
+use super::bindings::paludis_match_package;
+use super::bindings::paludis_packagedepspecdata_additional_requirements;
+use super::bindings::paludis_packagedepspecdata_as_string;
+use super::bindings::paludis_packagedepspecdata_name_part;
+use super::bindings::paludis_packagedepspecdata_path;
+use super::bindings::paludis_packagedepspecdata_repository;
+use super::bindings::paludis_packagedepspecdata_slot_requirement;
+use super::bindings::paludis_packagedepspecdata_version_requirements;
+use super::bindings::paludis_packagedepspecdata_version_requirements_and;
+use super::bindings::paludis_parse_user_package_dep_spec;
+use super::bindings::paludis_set_contents_flat;
+use super::bindings::paludis_set_contents_package;
+
+use super::packageid::new_version_spec;
+use super::Environment;
+use super::PackageID;
+use super::Result;
+use super::VersionSpec;
 
 #[derive(Debug)]
 pub enum DependencySpecTree {
     None,
     NamedSet(String),
     Labels(Vec<DependenciesLabel>),
-    Package(PackageDepSpecData),
+    Package(PackageDepSpec),
+    /// A blocker, e.g. "!dev-lang/rust" (weak) or "!!dev-lang/rust" (strong).
+    Block {
+        spec: PackageDepSpec,
+        strong: bool,
+        text: String,
+    },
     Conditional(ConditionalDepSpecData, Vec<DependencySpecTree>),
+    /// Any of the children is enough, e.g. "|| ( a b )".
+    Any(Vec<DependencySpecTree>),
     All(Vec<DependencySpecTree>),
 }
 
@@ -42,17 +73,17 @@ pub fn new_dependencyspectree(ptr: SharedPtr<u64>) -> DependencySpecTree {
             DependencySpecTree::Labels(res)
         }
         // paludis::PackageDepSpec
-        2 => DependencySpecTree::Package(new_packagedepspec_data(
-            paludis_dependencyspectree_package(ptr),
-        )),
-        // void visit(
-        //     const paludis::DependencySpecTree::NodeType<paludis::BlockDepSpec>::Type
-        //         &) {
-        //     t = 3;
-        // }
+        2 => {
+            DependencySpecTree::Package(new_packagedepspec(paludis_dependencyspectree_package(ptr)))
+        }
+        // paludis::BlockDepSpec
         3 => {
-            // println!("aa\n\n\n\n");
-            DependencySpecTree::None
+            let text = paludis_dependencyspectree_block_text(ptr.clone());
+            DependencySpecTree::Block {
+                spec: new_packagedepspec(paludis_dependencyspectree_block_package(ptr)),
+                strong: text.starts_with("!!"),
+                text,
+            }
         }
 
         // paludis::ConditionalDepSpec
@@ -70,14 +101,16 @@ pub fn new_dependencyspectree(ptr: SharedPtr<u64>) -> DependencySpecTree {
                 res,
             )
         }
-        // void
-        // visit(const paludis::DependencySpecTree::NodeType<paludis::AnyDepSpec>::Type
-        //           &) {
-        //     t = 5;
-        // }
+        // paludis::AnyDepSpec
         5 => {
-            // println!("dd\n\n\n\n");
-            DependencySpecTree::None
+            let mut res = Vec::new();
+            for i in 0..paludis_dependencyspectree_any_len(ptr.clone()) {
+                res.push(new_dependencyspectree(paludis_dependencyspectree_any_val(
+                    ptr.clone(),
+                    i,
+                )));
+            }
+            DependencySpecTree::Any(res)
         }
 
         // paludis::AllDepSpec
@@ -95,6 +128,111 @@ pub fn new_dependencyspectree(ptr: SharedPtr<u64>) -> DependencySpecTree {
     }
 }
 
+/// A spec tree that is not a [`DependencySpecTree`]: plain text, required use, licenses or URIs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpecTree {
+    All(Vec<SpecTree>),
+    /// Any of the children is enough, e.g. "|| ( GPL-2 MIT )".
+    Any(Vec<SpecTree>),
+    /// Exactly one of the children, e.g. "^^ ( a b )".
+    ExactlyOne(Vec<SpecTree>),
+    /// At most one of the children, e.g. "?? ( a b )".
+    AtMostOne(Vec<SpecTree>),
+    /// Children depending on a condition, e.g. "doc? ( ... )".
+    Conditional(String, Vec<SpecTree>),
+    /// A label, e.g. "fetch+mirror:".
+    Label(String),
+    /// A plain text token, a license, an URI, ...
+    Leaf(String),
+}
+
+/// Build a spec tree from {kind, text, number of children} triples, children following their parent.
+pub fn new_spec_tree(flat: Vec<String>) -> SpecTree {
+    fn node(flat: &mut std::vec::IntoIter<String>) -> SpecTree {
+        let kind = flat.next().unwrap_or_default();
+        let text = flat.next().unwrap_or_default();
+        let len = flat
+            .next()
+            .unwrap_or_default()
+            .parse::<usize>()
+            .unwrap_or(0);
+        let children = (0..len).map(|_| node(flat)).collect();
+
+        match kind.as_str() {
+            "0" => SpecTree::All(children),
+            "1" => SpecTree::Any(children),
+            "2" => SpecTree::ExactlyOne(children),
+            "3" => SpecTree::AtMostOne(children),
+            "4" => SpecTree::Conditional(text, children),
+            "5" => SpecTree::Label(text),
+            _ => SpecTree::Leaf(text),
+        }
+    }
+
+    node(&mut flat.into_iter())
+}
+
+/// Contents of a named set, see [set](crate::Environment::set).
+#[derive(Debug, Clone)]
+pub enum SetSpecTree {
+    All(Vec<SetSpecTree>),
+    Package(PackageDepSpec),
+    /// Another set, e.g. "system" in "everything", whose contents are given by its own [set](crate::Environment::set).
+    NamedSet(String),
+}
+
+impl SetSpecTree {
+    /// Package specs of the set, without expanding the sets it contains.
+    pub fn packages(&self) -> Vec<&PackageDepSpec> {
+        match self {
+            SetSpecTree::All(children) => children.iter().flat_map(|c| c.packages()).collect(),
+            SetSpecTree::Package(spec) => vec![spec],
+            SetSpecTree::NamedSet(_) => Vec::new(),
+        }
+    }
+
+    /// Names of the sets contained in the set.
+    pub fn sets(&self) -> Vec<&str> {
+        match self {
+            SetSpecTree::All(children) => children.iter().flat_map(|c| c.sets()).collect(),
+            SetSpecTree::Package(_) => Vec::new(),
+            SetSpecTree::NamedSet(name) => vec![name.as_str()],
+        }
+    }
+}
+
+/// Build a set tree from the {kind, text, number of children} triples of its contents.
+pub fn new_set_spec_tree(contents: SharedPtr<u64>) -> SetSpecTree {
+    fn node(
+        contents: &SharedPtr<u64>,
+        flat: &mut std::vec::IntoIter<String>,
+        packages: &mut u64,
+    ) -> SetSpecTree {
+        let kind = flat.next().unwrap_or_default();
+        let text = flat.next().unwrap_or_default();
+        let len = flat
+            .next()
+            .unwrap_or_default()
+            .parse::<usize>()
+            .unwrap_or(0);
+
+        match kind.as_str() {
+            "1" => {
+                *packages += 1;
+                SetSpecTree::Package(new_packagedepspec(paludis_set_contents_package(
+                    contents,
+                    *packages - 1,
+                )))
+            }
+            "2" => SetSpecTree::NamedSet(text),
+            _ => SetSpecTree::All((0..len).map(|_| node(contents, flat, packages)).collect()),
+        }
+    }
+
+    let flat = paludis_set_contents_flat(&contents);
+    node(&contents, &mut flat.into_iter(), &mut 0)
+}
+
 pub struct DependenciesLabel {
     ptr: SharedPtr<u64>,
 }
@@ -116,24 +254,344 @@ impl DependenciesLabel {
     }
 }
 
-pub struct PackageDepSpecData {
-    ptr: SharedPtr<u64>,
+/// Options for [PackageDepSpec::parse_user_with_options], see paludis UserPackageDepSpecOptions.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UserPackageDepSpecOptions {
+    /// Allow wildcards in the category and package name parts, e.g. "*/rust" or "dev-lang/*".
+    pub allow_wildcards: bool,
+    /// Fail with [`Error::GotASetNotAPackageDepSpec`](crate::Error::GotASetNotAPackageDepSpec) if the spec is a set name.
+    pub throw_if_set: bool,
+    /// Don't try to find the category of a package name given without one.
+    pub no_disambiguation: bool,
+}
+
+/// Options for [PackageDepSpec::matches_with_options], see paludis MatchPackageOptions.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MatchPackageOptions {
+    /// Don't check the additional requirements (choices, keys, ...) of the spec.
+    pub ignore_additional_requirements: bool,
+    /// Only skip the choice (USE) requirements of the spec.
+    pub ignore_choice_requirements: bool,
 }
 
-pub fn new_packagedepspec_data(ptr: SharedPtr<u64>) -> PackageDepSpecData {
-    PackageDepSpecData { ptr }
+/// Operator of a version requirement, e.g. the ">=" of ">=dev-lang/rust-1.70".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionOperator {
+    /// <
+    Less,
+    /// <=
+    LessEqual,
+    /// =
+    Equal,
+    /// ~, equal ignoring the revision.
+    Tilde,
+    /// \>
+    Greater,
+    /// \>=
+    GreaterEqual,
+    /// =*, the version starts with the given components, e.g. "=1.2*" matches 1.2.3 but not 1.20.
+    NiceEqualStar,
+    /// =* as understood by older EAPIs, the version string starts with the given string, e.g. "=1.2*" matches 1.2.3 and 1.20.
+    StupidEqualStar,
+    /// ~>, greater or equal with the same leading components.
+    TildeGreater,
 }
 
-impl Debug for PackageDepSpecData {
+impl From<u8> for VersionOperator {
+    fn from(n: u8) -> Self {
+        match n {
+            0 => VersionOperator::Less,
+            1 => VersionOperator::LessEqual,
+            2 => VersionOperator::Equal,
+            3 => VersionOperator::Tilde,
+            4 => VersionOperator::Greater,
+            5 => VersionOperator::GreaterEqual,
+            6 => VersionOperator::NiceEqualStar,
+            7 => VersionOperator::StupidEqualStar,
+            8 => VersionOperator::TildeGreater,
+            _ => unreachable!("version operators are numbered from 0 to 8"),
+        }
+    }
+}
+
+/// A version requirement of a [`PackageDepSpec`], e.g. ">=1.70".
+#[derive(Debug, PartialEq)]
+pub struct VersionRequirement {
+    pub operator: VersionOperator,
+    pub version: VersionSpec,
+}
+
+/// The slot requirement of a [`PackageDepSpec`], e.g. the ":stable" of "dev-lang/rust:stable".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlotRequirement {
+    /// ":stable", the slot must be the given one.
+    Slot(String),
+    /// ":stable/1.70", the slot and the subslot must be the given ones.
+    SlotAndSubslot(String, String),
+    /// ":=", any slot, the dependent is rebuilt if the installed one changes.
+    AnyLocked,
+    /// ":stable=", the given slot, the dependent is rebuilt if its installed subslot changes.
+    SlotLocked(String),
+    /// ":*", any slot.
+    Any,
+    /// Any other requirement, as written, e.g. a slot rewritten by paludis.
+    Other(String),
+}
+
+/// Build a slot requirement from {kind, slot, subslot or raw requirement}, None if empty.
+fn new_slot_requirement(raw: Vec<String>) -> Option<SlotRequirement> {
+    let mut raw = raw.into_iter();
+    let (kind, slot, other) = (raw.next()?, raw.next()?, raw.next()?);
+    Some(match kind.as_str() {
+        "0" => SlotRequirement::Slot(slot),
+        "1" => SlotRequirement::SlotAndSubslot(slot, other),
+        "2" => SlotRequirement::AnyLocked,
+        "3" => SlotRequirement::SlotLocked(slot),
+        "4" => SlotRequirement::Any,
+        _ => SlotRequirement::Other(other),
+    })
+}
+
+/// How a [`ChoiceRequirement`] constrains a choice (USE flag).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChoiceRequirementKind {
+    /// "[llvm]", the choice must be enabled.
+    Enabled,
+    /// "[-doc]", the choice must be disabled.
+    Disabled,
+    /// "[llvm?]", the choice must be enabled if it is enabled for the dependent.
+    IfEnabled,
+    /// "[!doc?]", the choice must be disabled if it is disabled for the dependent.
+    IfDisabled,
+    /// "[llvm=]", the choice must be the same as for the dependent.
+    Equal,
+    /// "[!llvm=]", the choice must be the opposite of the dependent one.
+    NotEqual,
+}
+
+/// A choice (USE) requirement of a [`PackageDepSpec`], e.g. the "[-doc(+)]" of "dev-lang/rust[-doc(+)]".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChoiceRequirement {
+    /// Name of the choice, with its prefix if any, e.g. "doc" or "python_abis:3.11".
+    pub name: String,
+    pub kind: ChoiceRequirementKind,
+    /// Whether the choice is taken as enabled, "(+)", or disabled, "(-)", by packages that don't have it.
+    pub default: Option<bool>,
+}
+
+/// An additional requirement of a [`PackageDepSpec`], the part between brackets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdditionalRequirement {
+    Choice(ChoiceRequirement),
+    /// Any other requirement, as written, e.g. a metadata key requirement like ".SLOT=stable".
+    Other(String),
+}
+
+/// Parse a choice requirement as written by paludis, e.g. "-doc(+)" or "!llvm=", None if it isn't one.
+pub(crate) fn parse_choice_requirement(raw: &str) -> Option<ChoiceRequirement> {
+    let (negated, rest) = match raw.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, raw),
+    };
+    let (disabled, rest) = match rest.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let (suffix, rest) = match rest.strip_suffix('?') {
+        Some(rest) => (Some('?'), rest),
+        None => match rest.strip_suffix('=') {
+            Some(rest) => (Some('='), rest),
+            None => (None, rest),
+        },
+    };
+    let (default, name) = match rest.strip_suffix("(+)") {
+        Some(name) => (Some(true), name),
+        None => match rest.strip_suffix("(-)") {
+            Some(name) => (Some(false), name),
+            None => (None, rest),
+        },
+    };
+
+    let kind = match (negated, disabled, suffix) {
+        (false, false, None) => ChoiceRequirementKind::Enabled,
+        (false, true, None) => ChoiceRequirementKind::Disabled,
+        (false, false, Some('?')) => ChoiceRequirementKind::IfEnabled,
+        (true, false, Some('?')) => ChoiceRequirementKind::IfDisabled,
+        (false, false, Some('=')) => ChoiceRequirementKind::Equal,
+        (true, false, Some('=')) => ChoiceRequirementKind::NotEqual,
+        _ => return None,
+    };
+    let valid = |c: char| c.is_ascii_alphanumeric() || "_+-.:@".contains(c);
+    if name.is_empty() || name.starts_with('.') || !name.chars().all(valid) {
+        return None;
+    }
+
+    Some(ChoiceRequirement {
+        name: name.to_owned(),
+        kind,
+        default,
+    })
+}
+
+/// How the version requirements of a [`PackageDepSpec`] are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionRequirementsMode {
+    /// All the requirements must match.
+    And,
+    /// Any of the requirements must match.
+    Or,
+}
+
+/// A package dep spec, e.g. ">=dev-lang/rust-1.70:stable::arbor[llvm]".
+///
+/// Every part of the spec is optional, a spec with wildcards has no [package](#method.package) but a category or package name part.
+#[derive(Clone)]
+pub struct PackageDepSpec {
+    pub(crate) ptr: SharedPtr<u64>,
+}
+
+pub fn new_packagedepspec(ptr: SharedPtr<u64>) -> PackageDepSpec {
+    PackageDepSpec { ptr }
+}
+
+impl Debug for PackageDepSpec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.full_name());
+        f.write_str(&self.to_string());
         Ok(())
     }
 }
 
-impl PackageDepSpecData {
-    pub fn full_name(&self) -> String {
-        paludis_packagedepspecdata_fullname(self.ptr.to_owned())
+impl Display for PackageDepSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&paludis_packagedepspecdata_as_string(&self.ptr))
+    }
+}
+
+impl PackageDepSpec {
+    /// Parse a package dep spec as given by a user, with the default [`UserPackageDepSpecOptions`].
+    /// A package name without category is disambiguated using the environment.
+    pub fn parse_user(env: &Environment, spec: &str) -> Result<Self> {
+        Self::parse_user_with_options(env, spec, UserPackageDepSpecOptions::default())
+    }
+
+    /// Parse a package dep spec as given by a user.
+    pub fn parse_user_with_options(
+        env: &Environment,
+        spec: &str,
+        options: UserPackageDepSpecOptions,
+    ) -> Result<Self> {
+        Ok(new_packagedepspec(paludis_parse_user_package_dep_spec(
+            &env.ptr,
+            spec,
+            options.allow_wildcards,
+            options.throw_if_set,
+            options.no_disambiguation,
+        )?))
+    }
+
+    /// Test if a package ID satisfies this spec, with the default [`MatchPackageOptions`].
+    pub fn matches(&self, env: &Environment, id: &PackageID) -> Result<bool> {
+        self.matches_with_options(env, id, MatchPackageOptions::default())
+    }
+
+    /// Test if a package ID satisfies this spec, version, slot, repository and additional requirements included.
+    pub fn matches_with_options(
+        &self,
+        env: &Environment,
+        id: &PackageID,
+        options: MatchPackageOptions,
+    ) -> Result<bool> {
+        paludis_match_package(
+            &env.ptr,
+            &self.ptr,
+            &id.ptr,
+            options.ignore_additional_requirements,
+            options.ignore_choice_requirements,
+        )
+    }
+
+    /// Qualified package name, e.g. "dev-lang/rust", None if the spec has none (e.g. with wildcards).
+    pub fn package(&self) -> Option<String> {
+        paludis_packagedepspecdata_name_part(&self.ptr, 0)
+    }
+
+    /// Category name part of a wildcard spec, e.g. "dev-lang" for "dev-lang/*".
+    pub fn category_name_part(&self) -> Option<String> {
+        paludis_packagedepspecdata_name_part(&self.ptr, 1)
+    }
+
+    /// Package name part of a wildcard spec, e.g. "rust" for "*/rust".
+    pub fn package_name_part(&self) -> Option<String> {
+        paludis_packagedepspecdata_name_part(&self.ptr, 2)
+    }
+
+    /// Version requirements, e.g. ">=1.70".
+    /// Fails with [`Error::InternalError`](crate::Error::InternalError) on an operator this crate doesn't know.
+    pub fn version_requirements(&self) -> Result<Vec<VersionRequirement>> {
+        let raw = paludis_packagedepspecdata_version_requirements(&self.ptr)?;
+
+        let mut res = Vec::new();
+        let mut requirements = raw.into_iter();
+        while let (Some(operator), Some(version)) = (requirements.next(), requirements.next()) {
+            res.push(VersionRequirement {
+                operator: operator
+                    .parse::<u8>()
+                    .expect("version operators are numbers")
+                    .into(),
+                version: new_version_spec(version),
+            });
+        }
+        Ok(res)
+    }
+
+    /// How the version requirements are combined.
+    pub fn version_requirements_mode(&self) -> VersionRequirementsMode {
+        if paludis_packagedepspecdata_version_requirements_and(&self.ptr) {
+            VersionRequirementsMode::And
+        } else {
+            VersionRequirementsMode::Or
+        }
+    }
+
+    /// Slot requirement, e.g. ":stable" or ":=".
+    pub fn slot_requirement(&self) -> Option<SlotRequirement> {
+        new_slot_requirement(paludis_packagedepspecdata_slot_requirement(&self.ptr))
+    }
+
+    /// Repository the package must be in, e.g. "arbor" for "::arbor".
+    pub fn in_repository(&self) -> Option<String> {
+        paludis_packagedepspecdata_repository(&self.ptr, 0)
+    }
+
+    /// Repository the package must come from, e.g. "arbor" for "::arbor->".
+    pub fn from_repository(&self) -> Option<String> {
+        paludis_packagedepspecdata_repository(&self.ptr, 1)
+    }
+
+    /// Repository the package must be installable to, e.g. "installed" for "::installed?".
+    pub fn installable_to_repository(&self) -> Option<String> {
+        paludis_packagedepspecdata_repository(&self.ptr, 2)
+    }
+
+    /// Root the package must be installed at, e.g. "/" for "::/".
+    pub fn installed_at_path(&self) -> Option<PathBuf> {
+        paludis_packagedepspecdata_path(&self.ptr, 0).map(PathBuf::from)
+    }
+
+    /// Root the package must be installable to, e.g. "/" for "::/?".
+    pub fn installable_to_path(&self) -> Option<PathBuf> {
+        paludis_packagedepspecdata_path(&self.ptr, 1).map(PathBuf::from)
+    }
+
+    /// Additional requirements, mostly choice (USE) requirements, e.g. "llvm" and "-doc" for "[llvm][-doc]".
+    pub fn additional_requirements(&self) -> Vec<AdditionalRequirement> {
+        paludis_packagedepspecdata_additional_requirements(&self.ptr)
+            .into_iter()
+            .map(|r| match parse_choice_requirement(&r) {
+                Some(choice) => AdditionalRequirement::Choice(choice),
+                None => AdditionalRequirement::Other(r),
+            })
+            .collect()
     }
 }
 
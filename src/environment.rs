@@ -1,15 +1,48 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::bindings::paludis_environment_create_action_output_manager;
 use super::bindings::paludis_environment_create_sync_output_manager;
 use super::bindings::paludis_environment_fetch_repository;
 use super::bindings::paludis_environment_has_repository_named;
 use super::bindings::paludis_environment_new;
+use super::bindings::paludis_environment_repositories_cursor;
 use super::bindings::paludis_environment_repositories_names;
+use super::bindings::paludis_environment_set;
+use super::bindings::paludis_environment_set_names;
+use super::bindings::paludis_environment_world_package;
+use super::bindings::paludis_environment_world_set;
+use super::bindings::paludis_repositories_cursor_next;
 
+use super::dep_spec::new_set_spec_tree;
 use super::output_manager::*;
+use super::owners::owners_of;
+use super::owners::OwnersIndex;
 use super::repository::new_repository;
+use super::resolver::resolve;
+use super::selection::new_query;
+use super::sync::sync_all;
+use super::ActionKind;
+use super::ContentsEntry;
+use super::Error;
+use super::Generator;
+use super::OwnerMatch;
+use super::PackageDepSpec;
+use super::PackageID;
+use super::Query;
 use super::Repository;
+use super::ResolutionPlan;
+use super::ResolverOptions;
+use super::Result;
+use super::Selection;
+use super::SetSpecTree;
+use super::SyncPlan;
+use super::SyncReport;
+use super::Target;
 
 use cxx::SharedPtr;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OuputExclusivity {
     // Run in the background, produce no output
     Background,
@@ -45,63 +78,259 @@ impl CreateOutputManagerSyncInfo {
     }
 }
 
+pub struct CreateOutputManagerActionInfo<'env> {
+    pub id: PackageID<'env>,
+    pub action: ActionKind,
+    pub ouput_exculivity: OuputExclusivity,
+    pub summary: bool,
+}
+
+impl<'env> CreateOutputManagerActionInfo<'env> {
+    pub fn no_output(id: &PackageID<'env>, action: ActionKind) -> Self {
+        CreateOutputManagerActionInfo {
+            id: id.clone(),
+            action,
+            ouput_exculivity: OuputExclusivity::Background,
+            summary: false,
+        }
+    }
+}
+
 /// Represents a working environment, which contains an available packages database and provides various methods for querying package visibility and options.
 /// Holds a number of [`Repository`] instances.
 pub struct Environment {
-    ptr: SharedPtr<u64>,
+    pub(crate) ptr: SharedPtr<u64>,
+    /// Index of the installed contents, built by [owners_of](Environment::owners_of).
+    pub(crate) owners: Mutex<Option<OwnersIndex>>,
 }
 
 impl Environment {
     /// Create an environment from the given spec.
     /// A spec consisits of class:suffix both of which may be omitted.
     /// class is the environment class, e.g. paludis or portage, suffix is the configuration directory suffix.
-    /// If the configuration directory is empty, it will exit the program and throws "paludis::paludis_environment::PaludisConfigNoDirectoryError"
+    /// If the environment can't be created (e.g. the configuration directory is missing, [`Error::ConfigurationError`](crate::Error::ConfigurationError)), the paludis exception is returned.
+    pub fn try_new(spec: &str) -> Result<Self> {
+        Ok(Environment {
+            ptr: paludis_environment_new(spec)?,
+            owners: Mutex::new(None),
+        })
+    }
+
+    /// Same as [try_new](#method.try_new), but if the environment can't be created, it prints the error and exits the program.
+    /// For example, if the configuration directory is empty, it exits on "paludis::paludis_environment::PaludisConfigNoDirectoryError".
     pub fn new(spec: &str) -> Self {
-        Environment {
-            ptr: paludis_environment_new(spec),
-        }
+        Self::try_new(spec).unwrap_or_else(|e| {
+            eprintln!("error: \"{e}\"");
+            std::process::exit(1)
+        })
     }
 
-    /// Iterate over repositories the safe way.
+    /// Iterate over repositories.
     pub fn repositories<F>(&self, mut f: F)
     where
         F: FnMut(&Repository),
     {
-        for name in self.repositories_names() {
-            let r = self.fetch_repository(&name).unwrap();
+        for r in self.repositories_iter() {
             f(&r);
         }
     }
 
+    /// Lazy iterator over the repositories in the environment.
+    pub fn repositories_iter(&self) -> Repositories<'_> {
+        Repositories {
+            env: self,
+            ptr: paludis_environment_repositories_cursor(&self.ptr),
+        }
+    }
+
     /// Return the list of the names of the repositories in the environment.
     pub fn repositories_names(&self) -> Vec<String> {
         paludis_environment_repositories_names(&self.ptr)
     }
 
     /// Test if there is a repository named like provided in the environment.
+    /// An invalid repository name is never in the environment.
     pub fn has_repository_named(&self, repository: &str) -> bool {
         paludis_environment_has_repository_named(&self.ptr, repository)
     }
 
     /// Test if the repository exist, and if yes, fetch it.
-    /// Be carefull, because reposository depends on each others, having multiple [`Repository`] at the same time could be dangerous (seg fault incomming).
-    /// Prefer [repositories](#method.repositories) iterator function.
-    pub fn fetch_repository(&self, repository: &str) -> Option<Repository> {
+    /// The returned [`Repository`] borrows the environment, so it can't outlive it, but any number of them can be held at the same time.
+    pub fn fetch_repository(&self, repository: &str) -> Option<Repository<'_>> {
         if !self.has_repository_named(repository) {
             None
         } else {
-            Some(new_repository(paludis_environment_fetch_repository(
-                &self.ptr, repository,
-            )))
+            paludis_environment_fetch_repository(&self.ptr, repository)
+                .ok()
+                .map(|ptr| new_repository(self, ptr))
         }
     }
 
+    /// Start a query for package IDs over every repository, see [`Query`].
+    ///
+    /// ```no_run
+    /// use paludis_rs::{Environment, Filter, Generator, Selection};
+    ///
+    /// let env = Environment::default();
+    /// let ids = env
+    ///     .query()
+    ///     .generator(Generator::Matches("dev-lang/rust".to_owned()))
+    ///     .filter(Filter::NotMasked)
+    ///     .select(Selection::BestVersionOnly);
+    /// ```
+    pub fn query(&self) -> Query<'_> {
+        new_query(self)
+    }
+
+    /// Fetch the only package ID matching a spec, e.g. a unique spec like "=dev-lang/rust-1.70.0:stable::arbor",
+    /// or a [canonical form](PackageID::canonical_form) like "dev-lang/rust-1.70.0:stable::arbor".
+    /// If no or several IDs match, [`Error::DidNotGetExactlyOneError`](crate::Error::DidNotGetExactlyOneError) is returned.
+    pub fn fetch_package_id(&self, spec: &str) -> Result<PackageID<'_>> {
+        let select = |spec: String| {
+            self.query()
+                .generator(Generator::Matches(spec))
+                .select(Selection::RequireExactlyOne)
+        };
+
+        let is_parse_error = |e: &Error| {
+            matches!(
+                e,
+                Error::PackageDepSpecError(_) | Error::NameError(_) | Error::BadVersionSpecError(_)
+            )
+        };
+
+        // A canonical form is a spec without its "=" operator, which doesn't parse as is.
+        // Once it parses with "=", the error of the lookup is the one that matters.
+        let mut ids = match select(spec.to_owned()) {
+            Err(e) if is_parse_error(&e) && !spec.starts_with(['=', '<', '>', '~']) => {
+                match select(format!("={}", spec)) {
+                    Err(retry) if is_parse_error(&retry) => return Err(e),
+                    res => res?,
+                }
+            }
+            res => res?,
+        };
+        Ok(ids.remove(0))
+    }
+
+    /// Names of the sets known to the environment, e.g. "world", "system" or user sets.
+    pub fn set_names(&self) -> Vec<String> {
+        paludis_environment_set_names(&self.ptr)
+    }
+
+    /// Contents of a named set, None if there is no such set.
+    ///
+    /// ```no_run
+    /// use paludis_rs::Environment;
+    ///
+    /// let env = Environment::default();
+    /// if let Some(world) = env.set("world").unwrap() {
+    ///     for spec in world.packages() {
+    ///         println!("{}", spec);
+    ///     }
+    /// }
+    /// ```
+    pub fn set(&self, name: &str) -> Result<Option<SetSpecTree>> {
+        Ok(paludis_environment_set(&self.ptr, name)?.map(new_set_spec_tree))
+    }
+
+    /// Add a target to the world set: the package of a spec, e.g. "dev-lang/rust", or a set, e.g. "system".
+    /// Returns false if it was already there, fails with [`Error::PackageDepSpecError`](crate::Error::PackageDepSpecError)
+    /// if the spec has no package name, or [`Error::NameError`](crate::Error::NameError) if there is no such set.
+    ///
+    /// ```no_run
+    /// use paludis_rs::{Environment, Target};
+    ///
+    /// let env = Environment::default();
+    /// env.add_to_world(&Target::Spec("dev-lang/rust".to_owned())).unwrap();
+    /// ```
+    pub fn add_to_world(&self, target: &Target) -> Result<bool> {
+        self.update_world(target, true)
+    }
+
+    /// Remove a target from the world set, even if its set doesn't exist anymore.
+    /// Returns false if it wasn't there.
+    pub fn remove_from_world(&self, target: &Target) -> Result<bool> {
+        self.update_world(target, false)
+    }
+
+    fn update_world(&self, target: &Target, add: bool) -> Result<bool> {
+        match target {
+            Target::Spec(spec) => {
+                let spec = PackageDepSpec::parse_user(self, spec)?;
+                paludis_environment_world_package(&self.ptr, &spec.ptr, add)
+            }
+            Target::Set(name) => paludis_environment_world_set(&self.ptr, name, add),
+        }
+    }
+
+    /// Resolve targets into a plan of decisions and jobs, like `cave resolve`.
+    ///
+    /// ```no_run
+    /// use paludis_rs::{Environment, ResolverOptions, Target};
+    ///
+    /// let env = Environment::default();
+    /// let plan = env
+    ///     .resolve(&[Target::Set("world".to_owned())], &ResolverOptions::default())
+    ///     .unwrap();
+    /// for d in plan.decisions.iter().filter(|d| d.taken) {
+    ///     println!("{:?} {}", d.kind, d.resolvent);
+    /// }
+    /// ```
+    pub fn resolve(
+        &self,
+        targets: &[Target],
+        options: &ResolverOptions,
+    ) -> Result<ResolutionPlan<'_>> {
+        resolve(self, targets, options)
+    }
+
+    /// Sync repositories like `cave sync`, in parallel but each one after its master repositories.
+    /// Each report tells what happened to a repository, in the order of the plan, e.g. an unknown repository has a [`NoSuchRepositoryError`](crate::Error::NoSuchRepositoryError) result.
+    ///
+    /// ```no_run
+    /// use paludis_rs::{Environment, SyncPlan};
+    ///
+    /// let env = Environment::default();
+    /// for r in env.sync_all(&SyncPlan::default()).unwrap() {
+    ///     println!("{}: {:?} in {:?}", r.repository, r.result, r.duration);
+    /// }
+    /// ```
+    pub fn sync_all(&self, plan: &SyncPlan) -> Result<Vec<SyncReport>> {
+        sync_all(self, plan)
+    }
+
+    /// Find the installed packages owning a path, like `cave print-owners`.
+    /// The contents of the installed packages are indexed on the first call, and the index reused by the next ones
+    /// until a package is installed or uninstalled, or [clear_owners_cache](Environment::clear_owners_cache) is called.
+    ///
+    /// ```no_run
+    /// use paludis_rs::{Environment, OwnerMatch};
+    ///
+    /// let env = Environment::default();
+    /// for (id, entry) in env.owners_of("/usr/bin/python3", OwnerMatch::Full).unwrap() {
+    ///     println!("{} owns {}", id.name(), entry.path().display());
+    /// }
+    /// ```
+    pub fn owners_of<P: AsRef<Path>>(
+        &self,
+        path: P,
+        how: OwnerMatch,
+    ) -> Result<Vec<(PackageID<'_>, ContentsEntry)>> {
+        owners_of(self, path.as_ref(), how)
+    }
+
+    /// Forget the index of the installed contents, e.g. after packages were installed by another process.
+    pub fn clear_owners_cache(&self) {
+        *self.owners.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
     /// Create an output manager to see repository sync infos.
     /// Need to be executed with root privilege if it output logs.
     pub fn create_sync_output_manager(
         &self,
         options: CreateOutputManagerSyncInfo,
-    ) -> Option<OutputManager> {
+    ) -> Result<OutputManager> {
         paludis_environment_create_sync_output_manager(
             &self.ptr,
             &options.repository,
@@ -110,6 +339,36 @@ impl Environment {
         )
         .map(|oe| new_output_manager(oe))
     }
+
+    /// Create an output manager for an action on a package ID, following the paludis output.conf.
+    /// It can be given to the action with [`OutputManagerFactory::Manager`](crate::OutputManagerFactory::Manager).
+    pub fn create_action_output_manager(
+        &self,
+        options: CreateOutputManagerActionInfo,
+    ) -> Result<OutputManager> {
+        paludis_environment_create_action_output_manager(
+            &self.ptr,
+            &options.id.ptr,
+            options.action.into(),
+            options.ouput_exculivity.into(),
+            options.summary,
+        )
+        .map(new_output_manager)
+    }
+}
+
+/// Lazy iterator over the repositories of an [`Environment`], see [repositories_iter](Environment::repositories_iter).
+pub struct Repositories<'env> {
+    env: &'env Environment,
+    ptr: SharedPtr<u64>,
+}
+
+impl<'env> Iterator for Repositories<'env> {
+    type Item = Repository<'env>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        paludis_repositories_cursor_next(&self.ptr).map(|ptr| new_repository(self.env, ptr))
+    }
 }
 
 impl Default for Environment {
@@ -0,0 +1,188 @@
+use std::fmt::Display;
+
+use super::FetchFailure;
+
+/// An exception thrown by paludis, caught on the C++ side and brought back to Rust.
+#[derive(Debug, Clone)]
+pub struct PaludisError {
+    class: String,
+    message: String,
+    backtrace: String,
+    fetch_failures: Vec<FetchFailure>,
+}
+
+pub(crate) fn new_paludis_error(class: String, message: String, backtrace: String) -> PaludisError {
+    PaludisError {
+        class,
+        message,
+        backtrace,
+        fetch_failures: Vec::new(),
+    }
+}
+
+impl PaludisError {
+    /// C++ class of the exception, e.g. "paludis::paludis_environment::PaludisConfigNoDirectoryError".
+    pub fn class(&self) -> &str {
+        &self.class
+    }
+
+    /// Message carried by the exception.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Paludis context backtrace of the exception, one context per line (empty if the exception doesn't come from paludis).
+    pub fn backtrace(&self) -> &str {
+        &self.backtrace
+    }
+
+    /// Files a fetch action couldn't get, empty if the exception doesn't come from a fetch.
+    pub fn fetch_failures(&self) -> &[FetchFailure] {
+        &self.fetch_failures
+    }
+}
+
+impl Display for PaludisError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.message.is_empty() {
+            write!(f, "{}", self.class)
+        } else {
+            write!(f, "{}: {}", self.class, self.message)
+        }
+    }
+}
+
+impl std::error::Error for PaludisError {}
+
+/// Errors returned by this crate, following the paludis exception hierarchy.
+///
+/// Each variant is the closest paludis base class of the thrown exception, the exact class stays available through [class](#method.class).
+/// For example, a "paludis::paludis_environment::PaludisConfigNoDirectoryError" is a [`Error::ConfigurationError`].
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// A name (package, category, repository, slot, ...) is invalid.
+    NameError(PaludisError),
+    /// A version spec is invalid.
+    BadVersionSpecError(PaludisError),
+    /// A package dep spec is invalid.
+    PackageDepSpecError(PaludisError),
+    /// A set name was given where a package dep spec was expected.
+    GotASetNotAPackageDepSpec(PaludisError),
+    /// A package doesn't exist.
+    NoSuchPackageError(PaludisError),
+    /// A package name without category matches more than one package.
+    AmbiguousPackageNameError(PaludisError),
+    /// A repository doesn't exist.
+    NoSuchRepositoryError(PaludisError),
+    /// A selection requiring exactly one package ID didn't get exactly one.
+    DidNotGetExactlyOneError(PaludisError),
+    /// A repository sync failed.
+    SyncFailedError(PaludisError),
+    /// A configuration is invalid or missing.
+    ConfigurationError(PaludisError),
+    /// An action (install, fetch, ...) failed.
+    ActionFailedError(PaludisError),
+    /// An action (install, fetch, ...) was aborted.
+    ActionAbortedError(PaludisError),
+    /// A filesystem operation failed.
+    FSError(PaludisError),
+    /// Something that is not available in this build of paludis was requested.
+    NotAvailableError(PaludisError),
+    /// An internal paludis error, should not happen.
+    InternalError(PaludisError),
+    /// Any other paludis exception.
+    Exception(PaludisError),
+    /// An exception that doesn't come from paludis.
+    Other(PaludisError),
+}
+
+/// Result type of this crate.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Build an [`Error`] from the paludis base class name found on the C++ side.
+pub(crate) fn new_error(kind: &str, e: PaludisError) -> Error {
+    match kind {
+        "NameError" => Error::NameError(e),
+        "BadVersionSpecError" => Error::BadVersionSpecError(e),
+        "PackageDepSpecError" => Error::PackageDepSpecError(e),
+        "GotASetNotAPackageDepSpec" => Error::GotASetNotAPackageDepSpec(e),
+        "NoSuchPackageError" => Error::NoSuchPackageError(e),
+        "AmbiguousPackageNameError" => Error::AmbiguousPackageNameError(e),
+        "NoSuchRepositoryError" => Error::NoSuchRepositoryError(e),
+        "DidNotGetExactlyOneError" => Error::DidNotGetExactlyOneError(e),
+        "SyncFailedError" => Error::SyncFailedError(e),
+        "ConfigurationError" => Error::ConfigurationError(e),
+        "ActionFailedError" => Error::ActionFailedError(e),
+        "ActionAbortedError" => Error::ActionAbortedError(e),
+        "FSError" => Error::FSError(e),
+        "NotAvailableError" => Error::NotAvailableError(e),
+        "InternalError" => Error::InternalError(e),
+        "Exception" => Error::Exception(e),
+        _ => Error::Other(e),
+    }
+}
+
+impl Error {
+    /// The underlying exception.
+    pub fn paludis_error(&self) -> &PaludisError {
+        match self {
+            Error::NameError(e)
+            | Error::BadVersionSpecError(e)
+            | Error::PackageDepSpecError(e)
+            | Error::GotASetNotAPackageDepSpec(e)
+            | Error::NoSuchPackageError(e)
+            | Error::AmbiguousPackageNameError(e)
+            | Error::NoSuchRepositoryError(e)
+            | Error::DidNotGetExactlyOneError(e)
+            | Error::SyncFailedError(e)
+            | Error::ConfigurationError(e)
+            | Error::ActionFailedError(e)
+            | Error::ActionAbortedError(e)
+            | Error::FSError(e)
+            | Error::NotAvailableError(e)
+            | Error::InternalError(e)
+            | Error::Exception(e)
+            | Error::Other(e) => e,
+        }
+    }
+
+    /// C++ class of the exception.
+    pub fn class(&self) -> &str {
+        self.paludis_error().class()
+    }
+
+    /// Message carried by the exception.
+    pub fn message(&self) -> &str {
+        self.paludis_error().message()
+    }
+
+    /// Paludis context backtrace of the exception.
+    pub fn backtrace(&self) -> &str {
+        self.paludis_error().backtrace()
+    }
+
+    /// Files a fetch action couldn't get, see [`PaludisError::fetch_failures`].
+    pub fn fetch_failures(&self) -> &[FetchFailure] {
+        self.paludis_error().fetch_failures()
+    }
+
+    /// Attach the files a failed fetch couldn't get to its [`Error::ActionFailedError`].
+    pub(crate) fn with_fetch_failures(self, failures: Vec<FetchFailure>) -> Self {
+        match self {
+            Error::ActionFailedError(mut e) => {
+                e.fetch_failures = failures;
+                Error::ActionFailedError(e)
+            }
+            e => e,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.paludis_error().fmt(f)
+    }
+}
+
+/// The underlying [`PaludisError`] isn't a source, it is already displayed by the error itself.
+impl std::error::Error for Error {}
@@ -0,0 +1,324 @@
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Condvar;
+use std::sync::Mutex;
+
+use super::Action;
+use super::ActionOutcome;
+use super::Environment;
+use super::FetchActionOptions;
+use super::InstallActionOptions;
+use super::Job;
+use super::JobKind;
+use super::OutputManagerFactory;
+use super::ResolutionPlan;
+use super::Result;
+use super::UninstallActionOptions;
+
+/// What to do with the remaining jobs once one failed, like `cave execute-resolution --continue-on-failure`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContinueOnFailure {
+    /// Skip everything not started yet.
+    Never,
+    /// Keep fetching, but skip everything else.
+    IfFetching,
+    /// Skip the jobs requiring a failed or skipped job.
+    IfIndependent,
+    /// Run every job anyway.
+    Always,
+}
+
+/// Final state of a job, in the [`ExecuteReport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobState {
+    Succeeded,
+    Failed {
+        message: String,
+    },
+    /// Not run because of a failure, see [`ContinueOnFailure`].
+    Skipped,
+}
+
+/// Progress of a job, reported by [`JobEvent`]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobProgress {
+    Started,
+    Finished(JobState),
+}
+
+/// A change of state of a job, given to [on_event](ExecuteOptions::on_event).
+#[derive(Debug, Clone)]
+pub struct JobEvent {
+    /// Index of the job in [jobs](ResolutionPlan::jobs), or in [pretend_jobs](ResolutionPlan::pretend_jobs) for [`JobKind::Pretend`].
+    pub number: usize,
+    pub kind: JobKind,
+    pub spec: String,
+    pub progress: JobProgress,
+    /// Log of the job output, None if the output follows the paludis output.conf.
+    pub log_path: Option<PathBuf>,
+}
+
+/// Callback given the [`JobEvent`]s, see [on_event](ExecuteOptions::on_event).
+pub type JobEventHandler<'a> = Box<dyn Fn(&JobEvent) + Send + Sync + 'a>;
+
+/// Options of [`ResolutionPlan::execute`].
+pub struct ExecuteOptions<'a> {
+    /// Run the pretend jobs first, and nothing else if one of them fails.
+    pub pretend: bool,
+    /// Number of fetches run in parallel, ahead of the other jobs.
+    pub fetch_jobs: usize,
+    pub continue_on_failure: ContinueOnFailure,
+    /// Write the output of each job to a log in this directory.
+    pub log_dir: Option<PathBuf>,
+    /// Called on each job event, from the thread running the job.
+    pub on_event: Option<JobEventHandler<'a>>,
+}
+
+impl<'a> Default for ExecuteOptions<'a> {
+    fn default() -> Self {
+        ExecuteOptions {
+            pretend: true,
+            fetch_jobs: 1,
+            continue_on_failure: ContinueOnFailure::Never,
+            log_dir: None,
+            on_event: None,
+        }
+    }
+}
+
+impl<'a> ExecuteOptions<'a> {
+    /// Report job events to a callback, e.g. to send them on a channel.
+    pub fn on_event<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&JobEvent) + Send + Sync + 'a,
+    {
+        self.on_event = Some(Box::new(f));
+        self
+    }
+
+    fn emit(&self, job: &Job, number: usize, progress: JobProgress, log_path: &Option<PathBuf>) {
+        if let Some(f) = &self.on_event {
+            f(&JobEvent {
+                number,
+                kind: job.kind,
+                spec: job.spec.clone(),
+                progress,
+                log_path: log_path.clone(),
+            });
+        }
+    }
+
+    fn output(&self, job: &Job, number: usize) -> OutputManagerFactory {
+        match &self.log_dir {
+            None => OutputManagerFactory::default(),
+            Some(dir) => {
+                let name: String = job
+                    .spec
+                    .chars()
+                    .map(|c| {
+                        if c.is_alphanumeric() || c == '.' || c == '-' {
+                            c
+                        } else {
+                            '_'
+                        }
+                    })
+                    .collect();
+                OutputManagerFactory::Log {
+                    path: dir.join(format!("{:?}-{}-{}", job.kind, number, name).to_lowercase()),
+                }
+            }
+        }
+    }
+}
+
+/// Final state of each job, once [`ResolutionPlan::execute`] is done.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecuteReport {
+    pub pretend_jobs: Vec<JobState>,
+    pub jobs: Vec<JobState>,
+}
+
+impl ExecuteReport {
+    /// Test if every job succeeded.
+    pub fn succeeded(&self) -> bool {
+        self.pretend_jobs
+            .iter()
+            .chain(self.jobs.iter())
+            .all(|s| *s == JobState::Succeeded)
+    }
+}
+
+/// Run a job, failures being reported as its state.
+fn run_job(env: &Environment, job: &Job, output: OutputManagerFactory) -> JobState {
+    let run = || -> Result<Option<String>> {
+        match job.kind {
+            JobKind::Fetch => {
+                let action = Action::Fetch(FetchActionOptions {
+                    output,
+                    ..FetchActionOptions::default()
+                });
+                env.fetch_package_id(&job.spec)?.perform(&action)?;
+            }
+            JobKind::Install | JobKind::Pretend => {
+                let options = InstallActionOptions {
+                    destination: job.destination.clone(),
+                    replacing: job
+                        .replacing
+                        .iter()
+                        .map(|s| env.fetch_package_id(s))
+                        .collect::<Result<_>>()?,
+                    output,
+                };
+                let action = if job.kind == JobKind::Pretend {
+                    Action::Pretend(options)
+                } else {
+                    Action::Install(options)
+                };
+                if env.fetch_package_id(&job.spec)?.perform(&action)?
+                    == ActionOutcome::PretendFailed
+                {
+                    return Ok(Some("pretend checks failed".to_owned()));
+                }
+            }
+            JobKind::Uninstall => {
+                for spec in job.spec.split_whitespace() {
+                    let action = Action::Uninstall(UninstallActionOptions {
+                        output: output.clone(),
+                        ..UninstallActionOptions::default()
+                    });
+                    env.fetch_package_id(spec)?.perform(&action)?;
+                }
+            }
+        }
+        Ok(None)
+    };
+
+    match run() {
+        Ok(None) => JobState::Succeeded,
+        Ok(Some(message)) => JobState::Failed { message },
+        Err(e) => JobState::Failed {
+            message: e.to_string(),
+        },
+    }
+}
+
+/// Test if a job must be skipped, given the final states of the jobs done so far.
+pub(crate) fn skipped(
+    job: &Job,
+    states: &[Option<JobState>],
+    policy: ContinueOnFailure,
+    jobs: &[Job],
+) -> bool {
+    // Did a job fail, ignoring the jobs of the given kind?
+    let failed = |except: Option<JobKind>| {
+        states
+            .iter()
+            .zip(jobs)
+            .any(|(s, j)| matches!(s, Some(JobState::Failed { .. })) && except != Some(j.kind))
+    };
+    let requirement_broken = job
+        .requirements
+        .iter()
+        .any(|&r| states.get(r) != Some(&Some(JobState::Succeeded)));
+
+    match policy {
+        ContinueOnFailure::Never => requirement_broken || failed(None),
+        ContinueOnFailure::IfFetching if job.kind == JobKind::Fetch => {
+            requirement_broken || failed(Some(JobKind::Fetch))
+        }
+        ContinueOnFailure::IfFetching => requirement_broken || failed(None),
+        ContinueOnFailure::IfIndependent => requirement_broken,
+        ContinueOnFailure::Always => false,
+    }
+}
+
+/// Execute the jobs of a plan, like `cave execute-resolution`.
+pub fn execute_plan(
+    env: &Environment,
+    plan: &ResolutionPlan,
+    options: &ExecuteOptions,
+) -> Result<ExecuteReport> {
+    let mut report = ExecuteReport {
+        pretend_jobs: Vec::new(),
+        jobs: Vec::new(),
+    };
+
+    if options.pretend {
+        for (number, job) in plan.pretend_jobs.iter().enumerate() {
+            let log_path = options.output(job, number).log_path();
+            options.emit(job, number, JobProgress::Started, &log_path);
+            let state = run_job(env, job, options.output(job, number));
+            options.emit(job, number, JobProgress::Finished(state.clone()), &log_path);
+            report.pretend_jobs.push(state);
+        }
+
+        if !report.succeeded() {
+            for (number, job) in plan.jobs.iter().enumerate() {
+                options.emit(job, number, JobProgress::Finished(JobState::Skipped), &None);
+                report.jobs.push(JobState::Skipped);
+            }
+            return Ok(report);
+        }
+    }
+
+    let states: Mutex<Vec<Option<JobState>>> = Mutex::new(vec![None; plan.jobs.len()]);
+    let done = Condvar::new();
+
+    let run_numbered = |number: usize| {
+        let job = &plan.jobs[number];
+        let skip = {
+            let mut s = states.lock().unwrap();
+            while job
+                .requirements
+                .iter()
+                .any(|&r| r < number && s.get(r).is_some_and(|state| state.is_none()))
+            {
+                s = done.wait(s).unwrap();
+            }
+            skipped(job, &s, options.continue_on_failure, &plan.jobs)
+        };
+
+        let state = if skip {
+            options.emit(job, number, JobProgress::Finished(JobState::Skipped), &None);
+            JobState::Skipped
+        } else {
+            let output = options.output(job, number);
+            let log_path = output.log_path();
+            options.emit(job, number, JobProgress::Started, &log_path);
+            let state = run_job(env, job, output);
+            options.emit(job, number, JobProgress::Finished(state.clone()), &log_path);
+            state
+        };
+
+        states.lock().unwrap()[number] = Some(state);
+        done.notify_all();
+    };
+
+    let fetches: Vec<usize> = (0..plan.jobs.len())
+        .filter(|&n| plan.jobs[n].kind == JobKind::Fetch)
+        .collect();
+    let next_fetch = AtomicUsize::new(0);
+
+    std::thread::scope(|scope| {
+        for _ in 0..options.fetch_jobs.max(1).min(fetches.len()) {
+            scope.spawn(|| {
+                while let Some(&number) = fetches.get(next_fetch.fetch_add(1, Ordering::SeqCst)) {
+                    run_numbered(number);
+                }
+            });
+        }
+
+        for number in (0..plan.jobs.len()).filter(|&n| plan.jobs[n].kind != JobKind::Fetch) {
+            run_numbered(number);
+        }
+    });
+
+    report.jobs = states
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|s| s.unwrap_or(JobState::Skipped))
+        .collect();
+    Ok(report)
+}
@@ -19,7 +19,13 @@
 //!     let mut summaries: Vec<(String, String)> = Vec::new();
 //!
 //!     env.repositories(|r| {
-//!         summaries.push((r.name(), r.summary().unwrap_or(String::from("No summary"))))
+//!         summaries.push((
+//!             r.name(),
+//!             r.summary()
+//!                 .ok()
+//!                 .flatten()
+//!                 .unwrap_or(String::from("No summary")),
+//!         ))
 //!     });
 //!
 //!     for e in summaries {
@@ -28,24 +34,100 @@
 //! }
 //! ```
 
+mod action;
 mod bindings;
+mod choices;
+mod contents;
 mod dep_spec;
 pub mod environment;
+mod error;
+mod execute;
+pub mod logging;
+mod mask;
 mod metadata;
 mod output_manager;
+mod owners;
 mod packageid;
 mod repository;
+mod resolver;
+mod selection;
+mod sync;
 mod test;
 
+pub use action::Action;
+pub use action::ActionKind;
+pub use action::ActionOutcome;
+pub use action::FetchActionOptions;
+pub use action::FetchFailure;
+pub use action::FetchParts;
+pub use action::InstallActionOptions;
+pub use action::OutputManagerFactory;
+pub use action::SimpleActionOptions;
+pub use action::UninstallActionOptions;
 pub use bindings::extract_host_from_url;
+pub use choices::Choice;
+pub use choices::ChoiceOrigin;
+pub use choices::ChoiceValue;
+pub use choices::Choices;
+pub use choices::ChoicesIter;
+pub use contents::ContentsEntry;
+pub use dep_spec::AdditionalRequirement;
+pub use dep_spec::ChoiceRequirement;
+pub use dep_spec::ChoiceRequirementKind;
 pub use dep_spec::DependenciesLabel;
 pub use dep_spec::DependencySpecTree;
+pub use dep_spec::MatchPackageOptions;
+pub use dep_spec::PackageDepSpec;
+pub use dep_spec::SetSpecTree;
+pub use dep_spec::SlotRequirement;
+pub use dep_spec::SpecTree;
+pub use dep_spec::UserPackageDepSpecOptions;
+pub use dep_spec::VersionOperator;
+pub use dep_spec::VersionRequirement;
+pub use dep_spec::VersionRequirementsMode;
 pub use environment::Environment;
+pub use environment::Repositories;
+pub use error::Error;
+pub use error::PaludisError;
+pub use error::Result;
+pub use execute::ContinueOnFailure;
+pub use execute::ExecuteOptions;
+pub use execute::ExecuteReport;
+pub use execute::JobEvent;
+pub use execute::JobEventHandler;
+pub use execute::JobProgress;
+pub use execute::JobState;
+pub use mask::Mask;
+pub use mask::MaskOverrideReason;
+pub use mask::OverriddenMask;
 pub use metadata::MetadataKey;
 pub use metadata::MetadataKeyType;
 pub use metadata::MetadataValue;
+pub use output_manager::MessageType;
 pub use output_manager::OutputManager;
+pub use output_manager::OutputSink;
+pub use owners::OwnerMatch;
+pub use packageid::CanonicalForm;
 pub use packageid::PackageID;
+pub use packageid::Slot;
 pub use packageid::VersionSpec;
+pub use repository::CategoryNames;
+pub use repository::PackageIDs;
+pub use repository::PackageNames;
 pub use repository::Repository;
 pub use repository::SyncResult;
+pub use repository::SyncSource;
+pub use resolver::Decision;
+pub use resolver::DecisionKind;
+pub use resolver::Job;
+pub use resolver::JobKind;
+pub use resolver::ResolutionPlan;
+pub use resolver::ResolverOptions;
+pub use resolver::SlotPreference;
+pub use resolver::Target;
+pub use selection::Filter;
+pub use selection::Generator;
+pub use selection::Query;
+pub use selection::Selection;
+pub use sync::SyncPlan;
+pub use sync::SyncReport;
@@ -0,0 +1,135 @@
+//! Control of the paludis log, and forwarding of its messages to Rust.
+//!
+//! ```no_run
+//! use paludis_rs::logging::{self, LogLevel};
+//!
+//! logging::set_level(LogLevel::Warning);
+//! logging::set_handler(|m| eprintln!("paludis {:?}: {}", m.level, m.message));
+//! ```
+
+use std::panic::catch_unwind;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use super::bindings::paludis_log_forward;
+use super::bindings::paludis_log_set_log_level;
+
+/// Minimum level of the messages paludis logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    /// Problems in repositories, e.g. a package using an unknown EAPI.
+    Qa,
+    Warning,
+    /// Log nothing.
+    Silent,
+}
+
+impl Into<u8> for LogLevel {
+    fn into(self) -> u8 {
+        match self {
+            LogLevel::Debug => 0,
+            LogLevel::Qa => 1,
+            LogLevel::Warning => 2,
+            LogLevel::Silent => 3,
+        }
+    }
+}
+
+/// A message logged by paludis.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogMessage {
+    pub level: LogLevel,
+    /// Identifier of the message, e.g. "e.ebuild.unknown_eapi".
+    pub id: String,
+    /// What paludis was doing, outermost first, empty if the message was logged without context.
+    pub context: Vec<String>,
+    pub message: String,
+}
+
+type Handler = Arc<dyn Fn(&LogMessage) + Send + Sync>;
+
+static HANDLER: Mutex<Option<Handler>> = Mutex::new(None);
+
+/// Set the minimum level of the messages paludis logs, warnings by default.
+pub fn set_level(level: LogLevel) {
+    paludis_log_set_log_level(level.into());
+}
+
+/// Send the messages logged by paludis to a callback instead of stderr.
+/// It is called from the paludis logging thread, and may itself set or clear the handler.
+/// A panicking handler can't unwind through paludis, its panic is caught and the message dropped.
+pub fn set_handler<F>(handler: F)
+where
+    F: Fn(&LogMessage) + Send + Sync + 'static,
+{
+    *HANDLER.lock().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(handler));
+    paludis_log_forward(true);
+}
+
+/// Send the messages logged by paludis back to stderr.
+pub fn clear_handler() {
+    paludis_log_forward(false);
+    *HANDLER.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Turn the messages logged by paludis into `tracing` events, with `paludis.id` and `paludis.context` fields.
+/// Debug messages are debug events, QA messages info events and warnings warn events.
+#[cfg(feature = "tracing")]
+pub fn forward_to_tracing() {
+    set_handler(|m| {
+        let context = m.context.join("\n");
+        match m.level {
+            LogLevel::Debug => {
+                tracing::debug!(paludis.id = %m.id, paludis.context = %context, "{}", m.message)
+            }
+            LogLevel::Qa => {
+                tracing::info!(paludis.id = %m.id, paludis.context = %context, "{}", m.message)
+            }
+            _ => tracing::warn!(paludis.id = %m.id, paludis.context = %context, "{}", m.message),
+        }
+    });
+}
+
+/// Parse a message written by paludis, e.g.
+/// "cave@1700000000: [WARNING e.ebuild.unknown_eapi] When loading 'foo':\n  ... Unknown EAPI '9'\n".
+pub(crate) fn parse_message(text: &str) -> Option<LogMessage> {
+    let (_, rest) = text.split_once(": [")?;
+    let (header, rest) = rest.split_once("] ")?;
+    let (level, id) = header.split_once(' ')?;
+
+    let mut context: Vec<String> = rest
+        .trim_end_matches('\n')
+        .split("\n  ... ")
+        .map(|s| s.to_owned())
+        .collect();
+    let message = context.pop().unwrap_or_default();
+
+    Some(LogMessage {
+        level: match level {
+            "DEBUG" => LogLevel::Debug,
+            "QA" => LogLevel::Qa,
+            _ => LogLevel::Warning,
+        },
+        id: id.to_owned(),
+        context,
+        message,
+    })
+}
+
+/// Called by the log stream installed by [set_handler] with each message paludis writes.
+pub(crate) fn log_message(data: *const u8, len: usize) {
+    let text = String::from_utf8_lossy(unsafe { std::slice::from_raw_parts(data, len) });
+    // Called without the lock held, so that the handler can replace itself
+    let handler = HANDLER.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Some(handler) = handler {
+        let message = parse_message(&text).unwrap_or_else(|| LogMessage {
+            level: LogLevel::Warning,
+            id: String::new(),
+            context: Vec::new(),
+            message: text.trim_end().to_owned(),
+        });
+        let _ = catch_unwind(AssertUnwindSafe(|| handler(&message)));
+    }
+}
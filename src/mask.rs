@@ -0,0 +1,108 @@
+use std::path::PathBuf;
+
+/// A reason why a [`PackageID`](crate::PackageID) can't be installed, see [masks](crate::PackageID::masks).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mask {
+    /// Masked by the user configuration.
+    UserMask { description: String },
+    /// A key of the package (e.g. KEYWORDS or LICENSE) is not accepted.
+    UnacceptedMask {
+        description: String,
+        /// Raw name of the unaccepted key, e.g. "KEYWORDS".
+        key: String,
+    },
+    /// Masked by a repository mask file.
+    RepositoryMask {
+        description: String,
+        mask_file: PathBuf,
+        /// Comment lines explaining the mask.
+        comment: Vec<String>,
+    },
+    /// The package can't be installed, e.g. its EAPI is unsupported.
+    UnsupportedMask {
+        description: String,
+        explanation: String,
+    },
+}
+
+impl Mask {
+    /// Human readable description of the mask kind.
+    pub fn description(&self) -> &str {
+        match self {
+            Mask::UserMask { description }
+            | Mask::UnacceptedMask { description, .. }
+            | Mask::RepositoryMask { description, .. }
+            | Mask::UnsupportedMask { description, .. } => description,
+        }
+    }
+}
+
+/// Build masks from groups of {kind, description, detail, detail} strings.
+pub fn new_masks(raw: Vec<String>) -> Vec<Mask> {
+    let mut res = Vec::new();
+    let mut masks = raw.into_iter();
+    while let (Some(kind), Some(description), Some(detail), Some(other_detail)) =
+        (masks.next(), masks.next(), masks.next(), masks.next())
+    {
+        res.push(new_mask(kind, description, detail, other_detail));
+    }
+    res
+}
+
+fn new_mask(kind: String, description: String, detail: String, other_detail: String) -> Mask {
+    match kind.as_str() {
+        "1" => Mask::UnacceptedMask {
+            description,
+            key: detail,
+        },
+        "2" => Mask::RepositoryMask {
+            description,
+            mask_file: PathBuf::from(detail),
+            comment: other_detail.lines().map(String::from).collect(),
+        },
+        "3" => Mask::UnsupportedMask {
+            description,
+            explanation: detail,
+        },
+        _ => Mask::UserMask { description },
+    }
+}
+
+/// Why a mask was overridden.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskOverrideReason {
+    /// The package is unstable, but unstable packages are accepted.
+    AcceptedUnstable,
+    /// The user unmasked the package.
+    OverriddenByUser,
+}
+
+/// A mask that would apply to a [`PackageID`](crate::PackageID) but was overridden.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverriddenMask {
+    pub mask: Mask,
+    pub reason: MaskOverrideReason,
+}
+
+/// Build overridden masks from groups of a reason followed by a mask description, see [`new_masks`].
+pub fn new_overridden_masks(raw: Vec<String>) -> Vec<OverriddenMask> {
+    let mut res = Vec::new();
+    let mut masks = raw.into_iter();
+    while let (Some(reason), Some(kind), Some(description), Some(detail), Some(other_detail)) = (
+        masks.next(),
+        masks.next(),
+        masks.next(),
+        masks.next(),
+        masks.next(),
+    ) {
+        res.push(OverriddenMask {
+            mask: new_mask(kind, description, detail, other_detail),
+            reason: if reason == "0" {
+                MaskOverrideReason::AcceptedUnstable
+            } else {
+                MaskOverrideReason::OverriddenByUser
+            },
+        });
+    }
+    res
+}
@@ -1,5 +1,7 @@
-use core::panic;
 use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::time::SystemTime;
 
 use cxx::SharedPtr;
 
@@ -7,20 +9,43 @@ use super::bindings::paludis_dependencyspectree_all_len;
 use super::bindings::paludis_dependencyspectree_all_val;
 use super::bindings::paludis_dependencyspectree_type;
 use super::bindings::paludis_metadata_human_name;
+use super::bindings::paludis_metadata_keys_cursor_next;
 use super::bindings::paludis_metadata_raw_name;
 use super::bindings::paludis_metadata_type;
 use super::bindings::paludis_metadata_type_str;
+use super::bindings::paludis_metadata_value_bool;
+use super::bindings::paludis_metadata_value_choices;
 use super::bindings::paludis_metadata_value_dependencyspectree;
+use super::bindings::paludis_metadata_value_long;
 use super::bindings::paludis_metadata_value_map;
+use super::bindings::paludis_metadata_value_package_id;
+use super::bindings::paludis_metadata_value_package_ids;
+use super::bindings::paludis_metadata_value_path;
+use super::bindings::paludis_metadata_value_section;
+use super::bindings::paludis_metadata_value_slot;
+use super::bindings::paludis_metadata_value_spec_tree;
 use super::bindings::paludis_metadata_value_str;
 use super::bindings::paludis_metadata_value_string;
+use super::bindings::paludis_metadata_value_strings;
+use super::bindings::paludis_metadata_value_time;
 use super::bindings::paludis_metadata_value_type;
 
+use super::choices::new_choices;
+use super::contents::new_system_time;
 use super::dep_spec::new_dependencyspectree;
+use super::dep_spec::new_spec_tree;
+use super::packageid::new_package_id;
+use super::packageid::new_slot;
+use super::repository::new_package_ids;
 
+use super::Choices;
 use super::DependencySpecTree;
+use super::Environment;
 use super::PackageID;
 use super::Repository;
+use super::Result;
+use super::Slot;
+use super::SpecTree;
 
 /// The significance of a MetadataKey to a user.
 /// This is a hint to clients as to whether the key should be displayed when outputting information about a [`PackageID`] or [`Repository`].
@@ -88,15 +113,24 @@ impl ToString for MetadataKeyType {
 /// - A raw name : This is in a repository-defined format designed to closely represent the internal name. For example, ebuilds and VDB IDs use raw names like 'DESCRIPTION' and 'KEYWORDS', whereas CRAN uses names like 'Title' and 'BundleDescription'. The raw name is unique in a PackageID or Repository.
 /// - A human name : This is the name that should be used when outputting normally for a human to read.
 /// - A [`MetadataKeyType`] : This is a hint to clients as to whether the key should be displayed when outputting information about a package ID or Repository.
-pub struct MetadataKey {
+///
+/// A MetadataKey is borrowed from the [`Environment`] its [`PackageID`] or [`Repository`] comes from, and can't outlive it.
+pub struct MetadataKey<'env> {
+    env: &'env Environment,
     ptr: SharedPtr<u64>,
 }
 
-pub fn new_metadata_key(ptr: SharedPtr<u64>) -> MetadataKey {
-    MetadataKey { ptr }
+pub fn new_metadata_key(env: &Environment, ptr: SharedPtr<u64>) -> MetadataKey<'_> {
+    MetadataKey { env, ptr }
+}
+
+impl<'env> Debug for MetadataKey<'env> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.raw_name())
+    }
 }
 
-impl MetadataKey {
+impl<'env> MetadataKey<'env> {
     pub fn human_name(&self) -> String {
         paludis_metadata_human_name(self.ptr.to_owned())
     }
@@ -113,35 +147,101 @@ impl MetadataKey {
         paludis_metadata_type_str(self.ptr.to_owned())
     }
 
-    /// Use paludis to get the value behind a MetadataKey, and use paludis to stringify it.
-    pub fn value(&self) -> MetadataValue {
-        match paludis_metadata_value_type(self.ptr.to_owned()) {
-            0 => MetadataValue::String(paludis_metadata_value_string(self.ptr.to_owned())),
-            12 => {
-                // println!("{}", paludis_metadata_value_str(self.ptr.clone()));
-                MetadataValue::DependencySpecTree(new_dependencyspectree(
-                    paludis_metadata_value_dependencyspectree(self.ptr.to_owned()),
-                ))
+    /// Use paludis to get the value behind a MetadataKey, typed after the kind of the key.
+    pub fn value(&self) -> Result<MetadataValue<'env>> {
+        let k = self.ptr.to_owned();
+        Ok(match paludis_metadata_value_type(k.clone()) {
+            0 => MetadataValue::String(paludis_metadata_value_string(k)?),
+            1 => MetadataValue::Slot(new_slot(paludis_metadata_value_slot(k)?)),
+            2 => MetadataValue::Long(paludis_metadata_value_long(k)?),
+            3 => MetadataValue::Bool(paludis_metadata_value_bool(k)?),
+            4 => MetadataValue::Path(PathBuf::from(paludis_metadata_value_path(k)?)),
+            5 => MetadataValue::PackageID(new_package_id(
+                self.env,
+                paludis_metadata_value_package_id(k)?,
+            )),
+            6 => {
+                let (seconds, nanoseconds) = paludis_metadata_value_time(k)?;
+                MetadataValue::Time(new_system_time(seconds, nanoseconds))
             }
-            16 => MetadataValue::Map(paludis_metadata_value_map(self.ptr.clone())),
-            t => {
-                MetadataValue::String(t.to_string() + " " + &self.value_str())
-                // panic!("panic: unable to match MetadataValue type")
+            7 => MetadataValue::Choices(new_choices(self.env, paludis_metadata_value_choices(k)?)),
+            8 => MetadataValue::PlainTextSpecTree(new_spec_tree(paludis_metadata_value_spec_tree(
+                k,
+            )?)),
+            9 => MetadataValue::RequiredUseSpecTree(new_spec_tree(
+                paludis_metadata_value_spec_tree(k)?,
+            )),
+            10 => {
+                MetadataValue::LicenseSpecTree(new_spec_tree(paludis_metadata_value_spec_tree(k)?))
             }
-        }
+            11 => MetadataValue::SimpleURISpecTree(new_spec_tree(
+                paludis_metadata_value_spec_tree(k)?,
+            )),
+            12 => MetadataValue::DependencySpecTree(new_dependencyspectree(
+                paludis_metadata_value_dependencyspectree(k)?,
+            )),
+            13 => MetadataValue::FetchableURISpecTree(new_spec_tree(
+                paludis_metadata_value_spec_tree(k)?,
+            )),
+            14 => MetadataValue::KeywordNameSet(paludis_metadata_value_strings(k)?),
+            15 => MetadataValue::StringSet(paludis_metadata_value_strings(k)?),
+            16 => MetadataValue::Map(paludis_metadata_value_map(k)?),
+            17 => MetadataValue::StringSequence(paludis_metadata_value_strings(k)?),
+            18 => MetadataValue::Maintainers(paludis_metadata_value_strings(k)?),
+            19 => MetadataValue::PathSequence(
+                paludis_metadata_value_strings(k)?
+                    .into_iter()
+                    .map(PathBuf::from)
+                    .collect(),
+            ),
+            20 => MetadataValue::PackageIDSequence(
+                new_package_ids(self.env, paludis_metadata_value_package_ids(k)?).collect(),
+            ),
+            21 => {
+                let cursor = paludis_metadata_value_section(k)?;
+                MetadataValue::Section(
+                    std::iter::from_fn(|| paludis_metadata_keys_cursor_next(&cursor))
+                        .map(|ptr| new_metadata_key(self.env, ptr))
+                        .collect(),
+                )
+            }
+            _ => MetadataValue::String(self.value_str()?),
+        })
     }
 
     /// Use paludis to get the value behind a MetadataKey, and use paludis to stringify it.
     /// Prefer using other functions because you will have to do parsing and not all metadata can be directly transated to string.    
-    pub fn value_str(&self) -> String {
+    pub fn value_str(&self) -> Result<String> {
         paludis_metadata_value_str(self.ptr.to_owned())
     }
 }
 
+/// The value behind a [`MetadataKey`], one variant per kind of key.
 #[derive(Debug)]
-pub enum MetadataValue {
+pub enum MetadataValue<'env> {
     String(String),
-    Slot,
+    /// None if the key holds no slot.
+    Slot(Option<Slot>),
+    Long(i64),
+    Bool(bool),
+    Path(PathBuf),
+    PackageID(PackageID<'env>),
+    Time(SystemTime),
+    Choices(Choices<'env>),
+    PlainTextSpecTree(SpecTree),
+    RequiredUseSpecTree(SpecTree),
+    LicenseSpecTree(SpecTree),
+    SimpleURISpecTree(SpecTree),
     DependencySpecTree(DependencySpecTree),
+    FetchableURISpecTree(SpecTree),
+    KeywordNameSet(Vec<String>),
+    StringSet(Vec<String>),
     Map(HashMap<String, String>),
+    StringSequence(Vec<String>),
+    /// Maintainers, e.g. "Jane Doe <jane@example.org>".
+    Maintainers(Vec<String>),
+    PathSequence(Vec<PathBuf>),
+    PackageIDSequence(Vec<PackageID<'env>>),
+    /// Keys grouped in a section.
+    Section(Vec<MetadataKey<'env>>),
 }
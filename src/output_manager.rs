@@ -1,13 +1,45 @@
+use std::ffi::c_void;
+use std::fmt::Debug;
+use std::panic::catch_unwind;
+use std::panic::AssertUnwindSafe;
+use std::sync::Mutex;
+
 use cxx::SharedPtr;
 
 use super::bindings::paludis_output_manager_flush;
+use super::bindings::paludis_output_manager_from_sink;
+use super::bindings::paludis_output_manager_ignore_succeeded;
+use super::bindings::paludis_output_manager_message;
+use super::bindings::paludis_output_manager_nothing_more_to_come;
 use super::bindings::paludis_output_manager_succeeded;
 
+/// Receives the output and messages of an action or a sync.
+///
+/// Dropping an output manager ends its output with [nothing_more_to_come](OutputManager::nothing_more_to_come).
+/// Unless [succeeded](OutputManager::succeeded) was called, paludis then treats what it ran as failed, e.g. keeping its log files.
 pub struct OutputManager {
     pub(crate) ptr: SharedPtr<u64>,
+    done: bool,
+}
+
+impl Debug for OutputManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutputManager").finish_non_exhaustive()
+    }
 }
 
 impl OutputManager {
+    /// Create an output manager handing everything to a Rust [`OutputSink`], e.g. to stream a sync output elsewhere.
+    pub fn from_sink<S: OutputSink + 'static>(sink: S) -> Self {
+        let sink: Box<SinkBox> = Box::new(Mutex::new(SinkState {
+            sink: Box::new(sink),
+            panicked: false,
+        }));
+        new_output_manager(paludis_output_manager_from_sink(
+            Box::into_raw(sink) as *mut c_void
+        ))
+    }
+
     /// Called if an action succeeds. This can be used to, for example, unlink the files behind a to-disk logged output manager.  
     /// If an [OutputManager] is destroyed without having had this method called, it should assume failure. This might mean keeping rather than removing log files, for example.  
     /// Further messages and output may occur even after a call to this method.  
@@ -22,8 +54,167 @@ impl OutputManager {
     pub fn flush(&mut self) {
         paludis_output_manager_flush(self.ptr.clone());
     }
+
+    /// Show a message, e.g. to tell what is going on.
+    pub fn message(&mut self, kind: MessageType, text: &str) {
+        paludis_output_manager_message(&self.ptr, kind.into(), text);
+    }
+
+    /// Tell that no more output will come, e.g. so that a summary can be shown.
+    /// Called on drop if it wasn't before.
+    pub fn nothing_more_to_come(&mut self) {
+        if !self.done {
+            self.done = true;
+            paludis_output_manager_nothing_more_to_come(&self.ptr);
+        }
+    }
+
+    /// Tell that success won't be reported, later calls to [succeeded](OutputManager::succeeded) do nothing.
+    pub fn ignore_succeeded(&mut self) {
+        paludis_output_manager_ignore_succeeded(&self.ptr);
+    }
+}
+
+impl Drop for OutputManager {
+    fn drop(&mut self) {
+        self.nothing_more_to_come();
+    }
 }
 
 pub(crate) fn new_output_manager(ptr: SharedPtr<u64>) -> OutputManager {
-    OutputManager { ptr }
+    OutputManager { ptr, done: false }
+}
+
+/// Type of a message given to an [`OutputSink`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    Debug,
+    Info,
+    Warn,
+    Error,
+    /// A message also written to the paludis log.
+    Log,
+}
+
+impl Into<u8> for MessageType {
+    fn into(self) -> u8 {
+        match self {
+            MessageType::Debug => 0,
+            MessageType::Info => 1,
+            MessageType::Warn => 2,
+            MessageType::Error => 3,
+            MessageType::Log => 4,
+        }
+    }
+}
+
+impl From<u8> for MessageType {
+    fn from(kind: u8) -> Self {
+        match kind {
+            0 => MessageType::Debug,
+            1 => MessageType::Info,
+            2 => MessageType::Warn,
+            3 => MessageType::Error,
+            _ => MessageType::Log,
+        }
+    }
+}
+
+/// Receiver of everything written to an [`OutputManager`] made by [from_sink](OutputManager::from_sink).
+///
+/// Paludis may write from several threads, calls are serialized by the output manager.
+///
+/// The methods are called from C++, where a panic can't unwind: a panicking sink is caught,
+/// and isn't called anymore, so the rest of the output of the action or the sync is lost.
+///
+/// ```no_run
+/// use paludis_rs::{Environment, OutputManager, OutputSink};
+///
+/// struct Collect(Vec<u8>);
+///
+/// impl OutputSink for Collect {
+///     fn stdout(&mut self, data: &[u8]) {
+///         self.0.extend_from_slice(data);
+///     }
+///
+///     fn stderr(&mut self, data: &[u8]) {
+///         self.0.extend_from_slice(data);
+///     }
+/// }
+///
+/// let env = Environment::default();
+/// let mut om = OutputManager::from_sink(Collect(Vec::new()));
+/// env.fetch_repository("arbor").unwrap().sync("", &mut om).unwrap();
+/// ```
+pub trait OutputSink: Send {
+    /// Data written to the standard output, e.g. the output of a syncer or of a build.
+    fn stdout(&mut self, data: &[u8]);
+
+    /// Data written to the standard error.
+    fn stderr(&mut self, data: &[u8]);
+
+    /// A message from paludis, e.g. "Fetching dev-lang/rust".
+    fn message(&mut self, _kind: MessageType, _text: &str) {}
+
+    /// The action succeeded, see [succeeded](OutputManager::succeeded).
+    fn succeeded(&mut self) {}
+
+    /// The action doesn't report success, later calls to succeeded should be ignored.
+    fn ignore_succeeded(&mut self) {}
+
+    /// No more output will be written.
+    fn nothing_more_to_come(&mut self) {}
+
+    /// Buffered output should be shown, see [flush](OutputManager::flush).
+    fn flush(&mut self) {}
+}
+
+struct SinkState {
+    sink: Box<dyn OutputSink>,
+    /// The sink panicked once, it is ignored from then on.
+    panicked: bool,
+}
+
+type SinkBox = Mutex<SinkState>;
+
+/// Run a closure on the sink behind the pointer given to the C++ output manager.
+/// A panic can't cross the C++ caller, it is caught and the sink disabled instead.
+fn with_sink<F: FnOnce(&mut dyn OutputSink)>(sink: *mut c_void, f: F) {
+    let sink = unsafe { &*(sink as *const SinkBox) };
+    let mut state = sink.lock().unwrap_or_else(|e| e.into_inner());
+    if !state.panicked {
+        let s = state.sink.as_mut();
+        state.panicked = catch_unwind(AssertUnwindSafe(|| f(s))).is_err();
+    }
+}
+
+pub(crate) fn output_sink_write(sink: *mut c_void, is_stderr: bool, data: *const u8, len: usize) {
+    let data = unsafe { std::slice::from_raw_parts(data, len) };
+    with_sink(sink, |s| {
+        if is_stderr {
+            s.stderr(data)
+        } else {
+            s.stdout(data)
+        }
+    });
+}
+
+pub(crate) fn output_sink_message(sink: *mut c_void, kind: u8, data: *const u8, len: usize) {
+    let text = String::from_utf8_lossy(unsafe { std::slice::from_raw_parts(data, len) });
+    with_sink(sink, |s| s.message(kind.into(), &text));
+}
+
+/// Events: 0 succeeded, 1 ignore_succeeded, 2 nothing_more_to_come, 3 flush.
+pub(crate) fn output_sink_event(sink: *mut c_void, event: u8) {
+    with_sink(sink, |s| match event {
+        0 => s.succeeded(),
+        1 => s.ignore_succeeded(),
+        2 => s.nothing_more_to_come(),
+        _ => s.flush(),
+    });
+}
+
+pub(crate) fn output_sink_drop(sink: *mut c_void) {
+    let sink = unsafe { Box::from_raw(sink as *mut SinkBox) };
+    let _ = catch_unwind(AssertUnwindSafe(|| drop(sink)));
 }
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::Path;
+use std::path::PathBuf;
+
+use cxx::SharedPtr;
+
+use super::packageid::new_package_id;
+use super::ContentsEntry;
+use super::Environment;
+use super::Generator;
+use super::PackageID;
+use super::Result;
+use super::Selection;
+
+/// How a path is compared to the contents of the installed packages, like `cave print-owners --match`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OwnerMatch {
+    /// The whole path, e.g. "/usr/bin/python3".
+    Full,
+    /// The file name only, e.g. "python3" matches "/usr/bin/python3".
+    Basename,
+    /// Any part of the path, e.g. "bin/py" matches "/usr/bin/python3".
+    Partial,
+}
+
+/// Contents of every installed package, indexed by path and by file name.
+pub(crate) struct OwnersIndex {
+    entries: Vec<(SharedPtr<u64>, ContentsEntry)>,
+    by_path: HashMap<PathBuf, Vec<usize>>,
+    by_basename: HashMap<OsString, Vec<usize>>,
+}
+
+impl OwnersIndex {
+    /// Read the contents of the packages of every installed repository.
+    fn build(env: &Environment) -> Result<Self> {
+        let mut index = OwnersIndex {
+            entries: Vec::new(),
+            by_path: HashMap::new(),
+            by_basename: HashMap::new(),
+        };
+
+        for repository in env.repositories_iter() {
+            if repository.installed_root()?.is_none() {
+                continue;
+            }
+
+            let ids = env
+                .query()
+                .generator(Generator::InRepository(repository.name()))
+                .select(Selection::AllVersionsSorted)?;
+            for id in ids {
+                for entry in id.contents()? {
+                    let n = index.entries.len();
+                    index
+                        .by_path
+                        .entry(entry.path().to_owned())
+                        .or_default()
+                        .push(n);
+                    if let Some(name) = entry.path().file_name() {
+                        index
+                            .by_basename
+                            .entry(name.to_owned())
+                            .or_default()
+                            .push(n);
+                    }
+                    index.entries.push((id.ptr.clone(), entry));
+                }
+            }
+        }
+
+        Ok(index)
+    }
+
+    fn lookup(&self, path: &Path, how: OwnerMatch) -> Vec<usize> {
+        match how {
+            OwnerMatch::Full => self.by_path.get(path).cloned().unwrap_or_default(),
+            OwnerMatch::Basename => path
+                .file_name()
+                .and_then(|name| self.by_basename.get(name))
+                .cloned()
+                .unwrap_or_default(),
+            OwnerMatch::Partial => {
+                let pattern = path.to_string_lossy();
+                (0..self.entries.len())
+                    .filter(|&n| {
+                        self.entries[n]
+                            .1
+                            .path()
+                            .to_string_lossy()
+                            .contains(pattern.as_ref())
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Find the installed packages owning a path, building the index of their contents on the first call.
+pub fn owners_of<'env>(
+    env: &'env Environment,
+    path: &Path,
+    how: OwnerMatch,
+) -> Result<Vec<(PackageID<'env>, ContentsEntry)>> {
+    let mut cache = env.owners.lock().unwrap_or_else(|e| e.into_inner());
+    if cache.is_none() {
+        *cache = Some(OwnersIndex::build(env)?);
+    }
+
+    let index = cache.as_ref().unwrap();
+    Ok(index
+        .lookup(path, how)
+        .into_iter()
+        .map(|n| {
+            let (ptr, entry) = &index.entries[n];
+            (new_package_id(env, ptr.clone()), entry.clone())
+        })
+        .collect())
+}
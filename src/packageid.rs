@@ -1,46 +1,84 @@
 use cxx::SharedPtr;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::time::SystemTime;
 
+use super::bindings::paludis_packageid_canonical_form;
+use super::bindings::paludis_packageid_choices;
+use super::bindings::paludis_packageid_contents;
+use super::bindings::paludis_packageid_from_repositories;
+use super::bindings::paludis_packageid_installed_time;
+use super::bindings::paludis_packageid_is_installed;
+use super::bindings::paludis_packageid_masked;
+use super::bindings::paludis_packageid_masks;
 use super::bindings::paludis_packageid_metadata_exist;
 use super::bindings::paludis_packageid_metadata_key;
 use super::bindings::paludis_packageid_metadata_names;
 use super::bindings::paludis_packageid_name;
+use super::bindings::paludis_packageid_overridden_masks;
 use super::bindings::paludis_packageid_repository_name;
 use super::bindings::paludis_packageid_short_description;
+use super::bindings::paludis_packageid_slot;
+use super::bindings::paludis_packageid_supports_action;
+use super::bindings::paludis_packageid_uniquely_identifying_spec;
 use super::bindings::paludis_packageid_version;
 use super::bindings::paludis_versionspec_compare;
-use super::bindings::paludis_versionspec_eq;
 use super::bindings::paludis_versionspec_is_scm;
 use super::bindings::paludis_versionspec_valid;
 
+use super::action::perform_action;
+use super::choices::new_choices;
+use super::contents::new_contents;
+use super::contents::new_system_time;
+use super::dep_spec::new_packagedepspec;
+use super::mask::new_masks;
+use super::mask::new_overridden_masks;
 use super::metadata::new_metadata_key;
+use super::Action;
+use super::ActionKind;
+use super::ActionOutcome;
+use super::Choices;
+use super::ContentsEntry;
+use super::Environment;
+use super::Mask;
 use super::MetadataKey;
+use super::OverriddenMask;
+use super::PackageDepSpec;
 use super::Repository;
+use super::Result;
 
 /// Represents a version number (for example, 1.2.3b-r1).
+#[derive(Debug, Clone)]
 pub struct VersionSpec(String);
 
 impl VersionSpec {
-    /// Create a new valid version spec
-    pub fn new(v: &str) -> Option<Self> {
-        let res = Self(v.to_owned());
-
-        if res.is_valid() {
-            Some(res)
-        } else {
-            None
-        }
+    /// Create a new valid version spec, an invalid one is returned as [`Error::BadVersionSpecError`](crate::Error::BadVersionSpecError).
+    pub fn new(v: &str) -> Result<Self> {
+        paludis_versionspec_valid(v)?;
+        Ok(Self(v.to_owned()))
     }
 
     /// Is this an -scm package, or something pretending to be one?
-    // FIXME : Error handeling : cant tell if aspell-pt_BR-20131030.12.0 is scm (version validity)
-    pub fn is_scm(&self) -> bool {
+    /// Fails if paludis can't build this version spec, e.g. aspell-pt_BR-20131030.12.0.
+    pub fn is_scm(&self) -> Result<bool> {
         paludis_versionspec_is_scm(self.0.as_str())
     }
 
     /// Test if the VersionSpec paludis object is constructible from this version spec
     pub fn is_valid(&self) -> bool {
-        paludis_versionspec_valid(self.0.as_str())
+        paludis_versionspec_valid(self.0.as_str()).is_ok()
     }
+
+    /// Compare two version specs.
+    /// Fails if one of them can't be built by paludis, e.g. elinks-scm and elinks-0.12pre6-r1.
+    pub fn compare(&self, other: &Self) -> Result<std::cmp::Ordering> {
+        Ok(paludis_versionspec_compare(self.0.as_str(), other.0.as_str())?.cmp(&0))
+    }
+}
+
+pub fn new_version_spec(v: String) -> VersionSpec {
+    VersionSpec(v)
 }
 
 impl Into<String> for VersionSpec {
@@ -56,35 +94,78 @@ impl ToString for VersionSpec {
 }
 
 impl PartialEq for VersionSpec {
+    /// Same as [cmp](#method.cmp) returning equal.
     fn eq(&self, other: &Self) -> bool {
-        paludis_versionspec_eq(self.0.as_str(), other.0.as_str())
+        self.cmp(other) == std::cmp::Ordering::Equal
     }
 }
 
 impl Eq for VersionSpec {}
 
 impl PartialOrd for VersionSpec {
-    // FIXME : Error handling in paludis_versionspec_compare is not so good : package version like elinks-scm and elinks-0.12pre6-r1 are difficult to compare (version validity)
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        let cmp = paludis_versionspec_compare(self.0.as_str(), other.0.as_str());
-
-        if cmp == 0 {
-            Some(std::cmp::Ordering::Equal)
-        } else if cmp.is_negative() {
-            Some(std::cmp::Ordering::Less)
-        } else if cmp.is_positive() {
-            Some(std::cmp::Ordering::Greater)
-        } else {
-            // Classic quantum bug
-            None
-        }
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for VersionSpec {
+    /// Version specs are compared by paludis, or as strings if one of them can't be built, see [compare](#method.compare).
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other)
-            .unwrap_or_else(|| std::cmp::Ordering::Equal)
+        self.compare(other).unwrap_or_else(|_| self.0.cmp(&other.0))
+    }
+}
+
+/// The slot of a [`PackageID`], e.g. "stable/1.70" for a slot with a subslot.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Slot {
+    /// Slot as written by the package, e.g. "stable/1.70".
+    pub raw_value: String,
+    /// Slot used to decide if two packages can be installed in parallel, e.g. "stable".
+    pub parallel_value: String,
+    /// Slot and subslot used to match slot requirements, e.g. ("stable", "1.70").
+    pub match_values: (String, String),
+}
+
+/// Build a slot from {raw value, parallel value, match slot, match subslot}, None if empty.
+pub fn new_slot(raw: Vec<String>) -> Option<Slot> {
+    let mut raw = raw.into_iter();
+    match (raw.next(), raw.next(), raw.next(), raw.next()) {
+        (Some(raw_value), Some(parallel_value), Some(slot), Some(subslot)) => Some(Slot {
+            raw_value,
+            parallel_value,
+            match_values: (slot, subslot),
+        }),
+        _ => None,
+    }
+}
+
+impl Display for Slot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.raw_value)
+    }
+}
+
+/// How a [`PackageID`] is written by [canonical_form](PackageID::canonical_form).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanonicalForm {
+    /// Everything, e.g. "dev-lang/rust-1.70.0:stable::arbor".
+    Full,
+    /// Only the version, e.g. "1.70.0".
+    Version,
+    /// Everything but the version, e.g. "dev-lang/rust:stable::arbor".
+    NoVersion,
+    /// Everything but the name, e.g. "1.70.0:stable::arbor".
+    NoName,
+}
+
+impl Into<u8> for CanonicalForm {
+    fn into(self) -> u8 {
+        match self {
+            CanonicalForm::Full => 0,
+            CanonicalForm::Version => 1,
+            CanonicalForm::NoVersion => 2,
+            CanonicalForm::NoName => 3,
+        }
     }
 }
 
@@ -102,11 +183,15 @@ impl Ord for VersionSpec {
 /// - A collection (often empty) of masks. A masked package cannot be installed.
 ///
 /// A PackageID instance may support certain actions, which are represented via an Action subclass instance.
-pub struct PackageID {
-    ptr: SharedPtr<u64>,
+///
+/// A PackageID is borrowed from the [`Environment`] it comes from, and can't outlive it.
+#[derive(Clone)]
+pub struct PackageID<'env> {
+    pub(crate) env: &'env Environment,
+    pub(crate) ptr: SharedPtr<u64>,
 }
 
-impl PackageID {
+impl<'env> PackageID<'env> {
     pub fn name(&self) -> String {
         paludis_packageid_name(self.ptr.to_owned())
     }
@@ -115,11 +200,11 @@ impl PackageID {
         VersionSpec(paludis_packageid_version(self.ptr.to_owned()))
     }
 
-    pub fn short_description(&self) -> String {
+    pub fn short_description(&self) -> Result<String> {
         if self.metadata_exist("SUMMARY") {
             paludis_packageid_short_description(&self.ptr)
         } else {
-            "".to_owned()
+            Ok("".to_owned())
         }
     }
 
@@ -128,6 +213,88 @@ impl PackageID {
         paludis_packageid_repository_name(&self.ptr.to_owned())
     }
 
+    /// Write the package ID in one of the canonical forms used by paludis.
+    pub fn canonical_form(&self, form: CanonicalForm) -> String {
+        paludis_packageid_canonical_form(&self.ptr, form.into())
+    }
+
+    /// A spec matching this package ID and nothing else, e.g. "=dev-lang/rust-1.70.0:stable::arbor".
+    pub fn uniquely_identifying_spec(&self) -> PackageDepSpec {
+        new_packagedepspec(paludis_packageid_uniquely_identifying_spec(&self.ptr))
+    }
+
+    /// Slot of the package, None if its repository has no notion of slots.
+    pub fn slot(&self) -> Result<Option<Slot>> {
+        Ok(new_slot(paludis_packageid_slot(&self.ptr)?))
+    }
+
+    /// Test if the package supports the given kind of action, e.g. only installed packages can be uninstalled.
+    pub fn supports_action(&self, kind: ActionKind) -> bool {
+        paludis_packageid_supports_action(&self.ptr, kind.into())
+    }
+
+    /// Perform an action on the package, e.g. fetch its files or install it.
+    /// A failure is an [`Error::ActionFailedError`](crate::Error::ActionFailedError), telling the files a fetch couldn't get in its [fetch_failures](crate::Error::fetch_failures).
+    ///
+    /// ```no_run
+    /// use paludis_rs::{Action, Environment, FetchActionOptions, InstallActionOptions};
+    ///
+    /// let env = Environment::default();
+    /// let id = env.fetch_package_id("=dev-lang/rust-1.70.0::arbor").unwrap();
+    /// id.perform(&Action::Fetch(FetchActionOptions::default())).unwrap();
+    /// id.perform(&Action::Install(InstallActionOptions::new("installed"))).unwrap();
+    /// ```
+    pub fn perform(&self, action: &Action<'env>) -> Result<ActionOutcome> {
+        let outcome = perform_action(self, action);
+        if matches!(action, Action::Install(_) | Action::Uninstall(_)) {
+            self.env.clear_owners_cache();
+        }
+        outcome
+    }
+
+    /// Choices (USE flags, options, ...) of the package, empty if it has none.
+    pub fn choices(&self) -> Result<Choices<'env>> {
+        Ok(new_choices(self.env, paludis_packageid_choices(&self.ptr)?))
+    }
+
+    /// Test if the package is masked, i.e. can't be installed.
+    pub fn masked(&self) -> Result<bool> {
+        paludis_packageid_masked(&self.ptr)
+    }
+
+    /// Masks preventing the package from being installed, empty if it is not masked.
+    pub fn masks(&self) -> Result<Vec<Mask>> {
+        Ok(new_masks(paludis_packageid_masks(&self.ptr)?))
+    }
+
+    /// Masks that would apply to the package but were overridden, e.g. by accepting unstable keywords.
+    pub fn overridden_masks(&self) -> Result<Vec<OverriddenMask>> {
+        Ok(new_overridden_masks(paludis_packageid_overridden_masks(
+            &self.ptr,
+        )?))
+    }
+
+    /// Test if the package is installed, i.e. comes from a repository with an installed root.
+    pub fn is_installed(&self) -> bool {
+        paludis_packageid_is_installed(&self.env.ptr, &self.ptr)
+    }
+
+    /// Files, directories and links installed by the package, empty if it is not installed.
+    pub fn contents(&self) -> Result<Vec<ContentsEntry>> {
+        Ok(new_contents(paludis_packageid_contents(&self.ptr)?))
+    }
+
+    /// When the package was installed, None if it is not installed.
+    pub fn installed_time(&self) -> Result<Option<SystemTime>> {
+        Ok(paludis_packageid_installed_time(&self.ptr)?
+            .map(|(seconds, nanoseconds)| new_system_time(seconds, nanoseconds)))
+    }
+
+    /// Names of the repositories an installed package came from, e.g. ["arbor"], empty if unknown.
+    pub fn from_repositories(&self) -> Result<Vec<String>> {
+        paludis_packageid_from_repositories(&self.ptr)
+    }
+
     /// Test if a metadata is stored at the key provided, in this repository.
     pub fn metadata_exist(&self, metadata: &str) -> bool {
         paludis_packageid_metadata_exist(self.ptr.to_owned(), metadata)
@@ -139,26 +306,54 @@ impl PackageID {
     }
 
     /// Get metadata key by name
-    pub fn metadata_key(&self, metadata: &str) -> Option<MetadataKey> {
+    pub fn metadata_key(&self, metadata: &str) -> Option<MetadataKey<'env>> {
         if !self.metadata_exist(metadata) {
             None
         } else {
-            Some(new_metadata_key(paludis_packageid_metadata_key(
-                self.ptr.to_owned(),
-                metadata,
-            )))
+            Some(new_metadata_key(
+                self.env,
+                paludis_packageid_metadata_key(self.ptr.to_owned(), metadata),
+            ))
         }
     }
 
     /// Same as metadata_key, but the metadata key existancy is not checked: could segfault.
-    pub unsafe fn metadata_key_unchecked(&self, metadata: &str) -> MetadataKey {
-        new_metadata_key(paludis_packageid_metadata_key(
-            self.ptr.to_owned(),
-            metadata,
-        ))
+    pub unsafe fn metadata_key_unchecked(&self, metadata: &str) -> MetadataKey<'env> {
+        new_metadata_key(
+            self.env,
+            paludis_packageid_metadata_key(self.ptr.to_owned(), metadata),
+        )
+    }
+}
+
+impl<'env> Debug for PackageID<'env> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}::{}",
+            self.name(),
+            self.version().to_string(),
+            self.repository_name()
+        )
+    }
+}
+
+impl<'env> PartialEq for PackageID<'env> {
+    /// Two package IDs are equal if they have the same [uniquely identifying spec](#method.uniquely_identifying_spec).
+    fn eq(&self, other: &Self) -> bool {
+        self.uniquely_identifying_spec().to_string()
+            == other.uniquely_identifying_spec().to_string()
+    }
+}
+
+impl<'env> Eq for PackageID<'env> {}
+
+impl<'env> Hash for PackageID<'env> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.uniquely_identifying_spec().to_string().hash(state);
     }
 }
 
-pub fn new_package_id(ptr: SharedPtr<u64>) -> PackageID {
-    PackageID { ptr }
+pub fn new_package_id(env: &Environment, ptr: SharedPtr<u64>) -> PackageID<'_> {
+    PackageID { env, ptr }
 }
@@ -1,137 +1,232 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 use cxx::SharedPtr;
 
 use crate::output_manager::OutputManager;
 
+use super::bindings::paludis_category_names_cursor_next;
+use super::bindings::paludis_package_ids_cursor_next;
+use super::bindings::paludis_package_names_cursor_next;
 use super::bindings::paludis_repository_category_names;
+use super::bindings::paludis_repository_category_names_cursor;
+use super::bindings::paludis_repository_installed_root;
 use super::bindings::paludis_repository_metadata_exist;
 use super::bindings::paludis_repository_metadata_key;
 use super::bindings::paludis_repository_metadata_names;
 use super::bindings::paludis_repository_name;
-use super::bindings::paludis_repository_package_id_from_canonical_form;
-use super::bindings::paludis_repository_package_ids_canonical_form;
+use super::bindings::paludis_repository_package_ids_cursor;
 use super::bindings::paludis_repository_package_names;
+use super::bindings::paludis_repository_package_names_cursor;
 use super::bindings::paludis_repository_sync;
 
 use super::metadata::new_metadata_key;
 use super::packageid::new_package_id;
 use super::Environment;
+use super::Error;
 use super::MetadataKey;
 use super::MetadataKeyType;
+use super::MetadataValue;
 use super::PackageID;
+use super::Result;
 
-#[derive(Debug, Clone, Copy)]
+/// Outcome of [sync](Repository::sync).
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SyncResult {
     Success,
+    /// The repository has nothing to sync from.
     Skipped,
-    UnknownFailure,
+    /// The sync failed, with the message of the paludis SyncFailedError.
+    Failed {
+        message: String,
+    },
+}
+
+/// A source a [`Repository`] can sync from, see [sync_sources](Repository::sync_sources).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncSource {
+    /// Name of the source, empty for the default one, e.g. "local" for `sync = local: file:///srv/repo`.
+    pub name: String,
+    /// Where the repository is synced from, e.g. "git+https://git.exherbo.org/arbor.git".
+    pub url: String,
+    /// Options given to the syncer, e.g. "--branch=master".
+    pub options: String,
 }
 
 /// A Repository provides a representation of a physical repository to an [`Environment`]
-pub struct Repository {
+///
+/// A Repository is borrowed from the [`Environment`] it comes from, and can't outlive it.
+pub struct Repository<'env> {
+    env: &'env Environment,
     name: String,
     ptr: SharedPtr<u64>,
 }
 
-pub fn new_repository(ptr: SharedPtr<u64>) -> Repository {
+pub fn new_repository(env: &Environment, ptr: SharedPtr<u64>) -> Repository<'_> {
     let name = repository_name(ptr.to_owned());
-    Repository { ptr, name }
+    Repository { env, ptr, name }
 }
 
 fn repository_name(ptr: SharedPtr<u64>) -> String {
     paludis_repository_name(ptr)
 }
 
-impl Repository {
+impl<'env> Repository<'env> {
     /// Returns repository name.
     pub fn name(&self) -> String {
         self.name.to_owned()
     }
 
     /// Fetch category names.
-    pub fn category_names(&self) -> Vec<String> {
+    pub fn category_names(&self) -> Result<Vec<String>> {
         paludis_repository_category_names(self.ptr.to_owned())
     }
 
     /// Returns the names of all packages within the given category    
-    pub fn package_names_by_category(&self, category: &str) -> Vec<String> {
+    pub fn package_names_by_category(&self, category: &str) -> Result<Vec<String>> {
         paludis_repository_package_names(self.ptr.to_owned(), category)
     }
 
     /// Fetch all packages names.
-    pub fn package_names(&self) -> Vec<String> {
-        self.category_names()
-            .into_iter()
-            .flat_map(|c| self.package_names_by_category(&c))
-            .collect()
+    pub fn package_names(&self) -> Result<Vec<String>> {
+        let mut res = Vec::new();
+        for c in self.category_names()? {
+            res.append(&mut self.package_names_by_category(&c)?);
+        }
+        Ok(res)
+    }
+
+    /// Lazy iterator over the category names.
+    pub fn categories(&self) -> Result<CategoryNames> {
+        Ok(CategoryNames {
+            ptr: paludis_repository_category_names_cursor(&self.ptr)?,
+        })
+    }
+
+    /// Lazy iterator over the names of the packages within the given category.
+    pub fn packages_in(&self, category: &str) -> Result<PackageNames> {
+        Ok(PackageNames {
+            ptr: paludis_repository_package_names_cursor(&self.ptr, category)?,
+        })
+    }
+
+    /// Lazy iterator over the package IDs for the given package.
+    pub fn ids(&self, package: &str) -> Result<PackageIDs<'env>> {
+        Ok(new_package_ids(
+            self.env,
+            paludis_repository_package_ids_cursor(&self.ptr, package)?,
+        ))
     }
 
     /// Returns the package IDs for the given package
-    pub fn package_ids(&self, package: &str) -> Vec<PackageID> {
-        paludis_repository_package_ids_canonical_form(self.ptr.to_owned(), package)
-            .into_iter()
-            .map(|n| {
-                paludis_repository_package_id_from_canonical_form(self.ptr.to_owned(), package, &n)
-            })
-            .flatten()
-            .map(|ptr| new_package_id(ptr))
-            .collect()
+    pub fn package_ids(&self, package: &str) -> Result<Vec<PackageID<'env>>> {
+        Ok(self.ids(package)?.collect())
     }
 
-    pub fn sync(&mut self, source: &str, output_manager: &mut OutputManager) -> SyncResult {
-        let str = paludis_repository_sync(self.ptr.clone(), source, "", output_manager.ptr.clone());
+    /// Sync the repository from the given source, empty for the default one.
+    /// A failed sync is returned as [`SyncResult::Failed`], other errors as [`Error`](crate::Error).
+    pub fn sync(&mut self, source: &str, output_manager: &mut OutputManager) -> Result<SyncResult> {
+        self.sync_to_revision(source, "", output_manager)
+    }
 
-        match str.as_str() {
-            "success" => {
-                output_manager.succeeded();
-                output_manager.flush();
-                SyncResult::Success
-            }
-            "skipped" => {
-                output_manager.succeeded();
+    /// Sync the repository from the given source to an exact revision, e.g. a git commit.
+    /// An empty revision syncs to the latest one, like [sync](Repository::sync).
+    pub fn sync_to_revision(
+        &mut self,
+        source: &str,
+        revision: &str,
+        output_manager: &mut OutputManager,
+    ) -> Result<SyncResult> {
+        let synced = match paludis_repository_sync(
+            self.ptr.clone(),
+            source,
+            revision,
+            output_manager.ptr.clone(),
+        ) {
+            Ok(synced) => synced,
+            Err(Error::SyncFailedError(e)) => {
                 output_manager.flush();
-                SyncResult::Skipped
+                return Ok(SyncResult::Failed {
+                    message: e.message().to_owned(),
+                });
             }
-            "unknown" | _ => SyncResult::UnknownFailure,
+            Err(e) => return Err(e),
+        };
+
+        output_manager.succeeded();
+        output_manager.flush();
+        if synced {
+            Ok(SyncResult::Success)
+        } else {
+            Ok(SyncResult::Skipped)
         }
     }
 
-    fn string_medata_value(&self, key: &str) -> Option<String> {
+    /// Sources the repository can sync from, from its sync and sync_options metadata.
+    pub fn sync_sources(&self) -> Result<Vec<SyncSource>> {
+        let map = |key: &str| -> Result<HashMap<String, String>> {
+            match self.metadata_key(key).map(|k| k.value()).transpose()? {
+                Some(MetadataValue::Map(m)) => Ok(m),
+                _ => Ok(HashMap::new()),
+            }
+        };
+        let mut options = map("sync_options")?;
+
+        let mut res: Vec<SyncSource> = map("sync")?
+            .into_iter()
+            .map(|(name, url)| SyncSource {
+                options: options.remove(&name).unwrap_or_default(),
+                name,
+                url,
+            })
+            .collect();
+        res.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(res)
+    }
+
+    fn string_medata_value(&self, key: &str) -> Result<Option<String>> {
         if !self.metadata_exist(key) {
-            None
+            Ok(None)
         } else {
-            unsafe { Some(self.metadata_key_unchecked(key).value_str()) }
+            unsafe { Ok(Some(self.metadata_key_unchecked(key).value_str()?)) }
         }
     }
 
     /// The format metadata, if non-null, holds the repository's format. [`Repository`] implementations should not return [`None`] here, but clients should still check.
-    pub fn format(&self) -> Option<String> {
+    pub fn format(&self) -> Result<Option<String>> {
         self.string_medata_value("format")
     }
 
     /// The location metadata, if non-null, holds the file or directory containing the repository's data, the format of which depends on the value of format_key.
-    pub fn location(&self) -> Option<PathBuf> {
-        Some(PathBuf::from(self.string_medata_value("location")?))
+    pub fn location(&self) -> Result<Option<PathBuf>> {
+        Ok(self.string_medata_value("location")?.map(PathBuf::from))
     }
 
     /// The repository short description.
-    pub fn summary(&self) -> Option<String> {
+    pub fn summary(&self) -> Result<Option<String>> {
         self.string_medata_value("summary")
     }
 
+    /// Root the packages of an installed repository are installed into, e.g. "/", None if the repository isn't installed.
+    pub fn installed_root(&self) -> Result<Option<PathBuf>> {
+        Ok(paludis_repository_installed_root(&self.ptr)?
+            .pop()
+            .map(PathBuf::from))
+    }
+
     /// List of repositories this repository depend on.
-    pub fn master_repositories_names(&self) -> Vec<String> {
+    pub fn master_repositories_names(&self) -> Result<Vec<String>> {
         let key = "master_repository";
         if !self.metadata_exist(key) {
-            Vec::new()
+            Ok(Vec::new())
         } else {
             unsafe {
-                self.metadata_key_unchecked(key)
-                    .value_str()
+                Ok(self
+                    .metadata_key_unchecked(key)
+                    .value_str()?
                     .split('\n')
                     .map(|s| String::from(s))
-                    .collect()
+                    .collect())
             }
         }
     }
@@ -147,22 +242,66 @@ impl Repository {
     }
 
     /// Get metadata key by name
-    pub fn metadata_key(&self, metadata: &str) -> Option<MetadataKey> {
+    pub fn metadata_key(&self, metadata: &str) -> Option<MetadataKey<'env>> {
         if !self.metadata_exist(metadata) {
             None
         } else {
-            Some(new_metadata_key(paludis_repository_metadata_key(
-                self.ptr.to_owned(),
-                metadata,
-            )))
+            Some(new_metadata_key(
+                self.env,
+                paludis_repository_metadata_key(self.ptr.to_owned(), metadata),
+            ))
         }
     }
 
     /// Same as metadata_key, but the metadata key existancy is not checked: could segfault.
-    pub unsafe fn metadata_key_unchecked(&self, metadata: &str) -> MetadataKey {
-        new_metadata_key(paludis_repository_metadata_key(
-            self.ptr.to_owned(),
-            metadata,
-        ))
+    pub unsafe fn metadata_key_unchecked(&self, metadata: &str) -> MetadataKey<'env> {
+        new_metadata_key(
+            self.env,
+            paludis_repository_metadata_key(self.ptr.to_owned(), metadata),
+        )
+    }
+}
+
+/// Lazy iterator over the category names of a [`Repository`], see [categories](Repository::categories).
+pub struct CategoryNames {
+    ptr: SharedPtr<u64>,
+}
+
+impl Iterator for CategoryNames {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        paludis_category_names_cursor_next(&self.ptr)
+    }
+}
+
+/// Lazy iterator over the package names of a category in a [`Repository`], see [packages_in](Repository::packages_in).
+pub struct PackageNames {
+    ptr: SharedPtr<u64>,
+}
+
+impl Iterator for PackageNames {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        paludis_package_names_cursor_next(&self.ptr)
+    }
+}
+
+/// Lazy iterator over the [`PackageID`]s of a package in a [`Repository`], see [ids](Repository::ids).
+pub struct PackageIDs<'env> {
+    env: &'env Environment,
+    ptr: SharedPtr<u64>,
+}
+
+pub fn new_package_ids(env: &Environment, ptr: SharedPtr<u64>) -> PackageIDs<'_> {
+    PackageIDs { env, ptr }
+}
+
+impl<'env> Iterator for PackageIDs<'env> {
+    type Item = PackageID<'env>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        paludis_package_ids_cursor_next(&self.ptr).map(|ptr| new_package_id(self.env, ptr))
     }
 }
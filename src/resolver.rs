@@ -0,0 +1,278 @@
+use super::bindings::paludis_package_ids_cursor_next;
+use super::bindings::paludis_resolution_decision_id;
+use super::bindings::paludis_resolution_decision_replacing;
+use super::bindings::paludis_resolution_decisions;
+use super::bindings::paludis_resolution_jobs;
+use super::bindings::paludis_resolver_resolve;
+
+use super::execute::execute_plan;
+use super::packageid::new_package_id;
+use super::Environment;
+use super::ExecuteOptions;
+use super::ExecuteReport;
+use super::PackageID;
+use super::Result;
+
+/// Something to resolve, like the arguments of `cave resolve`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// A package spec, e.g. "dev-lang/rust" or ">=dev-lang/rust-1.70".
+    Spec(String),
+    /// A set name, e.g. "world" or "system".
+    Set(String),
+}
+
+/// Which slots of a package are considered, like `cave resolve --target-slots` and `--slots`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotPreference {
+    /// The best slot, or the installed ones if it can't be used.
+    BestOrInstalled,
+    /// The installed slots, or the best one if none is installed.
+    InstalledOrBest,
+    /// The best and the installed slots.
+    All,
+    /// Only the best slot.
+    Best,
+}
+
+impl Into<u8> for SlotPreference {
+    fn into(self) -> u8 {
+        match self {
+            SlotPreference::BestOrInstalled => 0,
+            SlotPreference::InstalledOrBest => 1,
+            SlotPreference::All => 2,
+            SlotPreference::Best => 3,
+        }
+    }
+}
+
+/// Options of [`Environment::resolve`], the default being a plain `cave resolve`.
+#[derive(Debug, Clone, Copy)]
+pub struct ResolverOptions {
+    /// Also update installed dependencies, like `--complete`.
+    pub complete: bool,
+    /// Reinstall everything, like `--everything`.
+    pub everything: bool,
+    pub target_slots: SlotPreference,
+    pub slots: SlotPreference,
+    /// Allow packages to be downgraded.
+    pub permit_downgrade: bool,
+    /// Allow packages to be uninstalled.
+    pub permit_uninstall: bool,
+    /// Make binaries of the targets instead of installing them, like `--make binaries`.
+    pub make_binaries: bool,
+}
+
+impl Default for ResolverOptions {
+    fn default() -> Self {
+        ResolverOptions {
+            complete: false,
+            everything: false,
+            target_slots: SlotPreference::BestOrInstalled,
+            slots: SlotPreference::InstalledOrBest,
+            permit_downgrade: false,
+            permit_uninstall: false,
+            make_binaries: false,
+        }
+    }
+}
+
+/// What the resolver decided for a resolvent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecisionKind {
+    /// Install the origin ID, new or replacing installed ones.
+    ChangeOrInstall,
+    /// Keep the installed ID.
+    Keep,
+    /// Remove the installed IDs.
+    Remove,
+    /// Break the installed ID, needs a confirmation.
+    Break,
+    /// Nothing suitable was found.
+    Unable,
+}
+
+impl From<u8> for DecisionKind {
+    fn from(kind: u8) -> Self {
+        match kind {
+            0 => DecisionKind::ChangeOrInstall,
+            1 => DecisionKind::Keep,
+            2 => DecisionKind::Remove,
+            3 => DecisionKind::Break,
+            _ => DecisionKind::Unable,
+        }
+    }
+}
+
+/// A decision of the resolver.
+#[derive(Debug, Clone)]
+pub struct Decision<'env> {
+    pub kind: DecisionKind,
+    /// What is resolved, e.g. "dev-lang/rust:stable -> installed".
+    pub resolvent: String,
+    /// The decision is part of the plan, untaken ones are only informative.
+    pub taken: bool,
+    /// Position of the change in the plan, None if nothing is done.
+    pub order: Option<usize>,
+    /// The ID installed, kept or broken.
+    pub origin: Option<PackageID<'env>>,
+    /// Name of the repository the origin is installed to.
+    pub destination: Option<String>,
+    /// IDs replaced or removed.
+    pub replacing: Vec<PackageID<'env>>,
+    /// Why the resolvent was considered, e.g. "target dev-lang/rust".
+    pub reasons: Vec<String>,
+    /// Confirmations needed before the decision can be carried out, e.g. "downgrade".
+    pub required_confirmations: Vec<String>,
+}
+
+/// Kinds of [`Job`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobKind {
+    Fetch,
+    Install,
+    Uninstall,
+    Pretend,
+}
+
+impl From<u8> for JobKind {
+    fn from(kind: u8) -> Self {
+        match kind {
+            0 => JobKind::Fetch,
+            1 => JobKind::Install,
+            2 => JobKind::Uninstall,
+            _ => JobKind::Pretend,
+        }
+    }
+}
+
+/// A job of a [`ResolutionPlan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    pub kind: JobKind,
+    /// Specs of the IDs concerned, space separated for uninstalls.
+    pub spec: String,
+    /// Name of the repository installed to, empty for fetches and uninstalls.
+    pub destination: String,
+    /// Specs of the installed IDs replaced by an install.
+    pub replacing: Vec<String>,
+    /// Numbers of the jobs that must be done before this one, i.e. their index in the job list.
+    pub requirements: Vec<usize>,
+}
+
+/// Result of [`Environment::resolve`].
+#[derive(Debug, Clone)]
+pub struct ResolutionPlan<'env> {
+    /// Decisions, the taken changes first in the order they must be done.
+    pub decisions: Vec<Decision<'env>>,
+    /// Jobs carrying out the taken changes, in order.
+    pub jobs: Vec<Job>,
+    /// Pretend jobs to check before running the jobs.
+    pub pretend_jobs: Vec<Job>,
+}
+
+impl<'env> ResolutionPlan<'env> {
+    /// Decisions needing confirmations, the plan should not be carried out until they are given.
+    pub fn unconfirmed(&self) -> impl Iterator<Item = &Decision<'env>> {
+        self.decisions
+            .iter()
+            .filter(|d| d.taken && !d.required_confirmations.is_empty())
+    }
+
+    /// Test if a taken decision could not be resolved.
+    pub fn has_errors(&self) -> bool {
+        self.decisions
+            .iter()
+            .any(|d| d.taken && d.kind == DecisionKind::Unable)
+    }
+
+    /// Run the jobs of the plan, like `cave execute-resolution`.
+    /// Job failures don't make it fail, they are reported in the [`ExecuteReport`] and to [on_event](ExecuteOptions::on_event).
+    ///
+    /// ```no_run
+    /// use paludis_rs::{Environment, ExecuteOptions, ResolverOptions, Target};
+    ///
+    /// let env = Environment::default();
+    /// let plan = env
+    ///     .resolve(&[Target::Spec("dev-lang/rust".to_owned())], &ResolverOptions::default())
+    ///     .unwrap();
+    /// let options = ExecuteOptions::default().on_event(|e| println!("{} {:?}", e.spec, e.state));
+    /// let report = plan.execute(&env, &options).unwrap();
+    /// assert!(report.succeeded());
+    /// ```
+    pub fn execute(&self, env: &Environment, options: &ExecuteOptions) -> Result<ExecuteReport> {
+        execute_plan(env, self, options)
+    }
+}
+
+/// Run the resolver on the targets.
+pub fn resolve<'env>(
+    env: &'env Environment,
+    targets: &[Target],
+    options: &ResolverOptions,
+) -> Result<ResolutionPlan<'env>> {
+    let mut specs = Vec::new();
+    let mut sets = Vec::new();
+    for t in targets {
+        match t {
+            Target::Spec(s) => specs.push(s.as_str()),
+            Target::Set(s) => sets.push(s.as_str()),
+        }
+    }
+
+    let r = paludis_resolver_resolve(
+        &env.ptr,
+        &specs.join("\n"),
+        &sets.join("\n"),
+        options.complete,
+        options.everything,
+        options.target_slots.into(),
+        options.slots.into(),
+        options.permit_downgrade,
+        options.permit_uninstall,
+        options.make_binaries,
+    )?;
+
+    let mut decisions = Vec::new();
+    let raw = paludis_resolution_decisions(&r);
+    for (i, d) in raw.chunks_exact(7).enumerate() {
+        let replacing = paludis_resolution_decision_replacing(&r, i as u64);
+        decisions.push(Decision {
+            kind: d[0].parse::<u8>().unwrap_or(u8::MAX).into(),
+            resolvent: d[1].clone(),
+            taken: d[2] == "1",
+            order: d[3].parse().ok(),
+            origin: paludis_resolution_decision_id(&r, i as u64)
+                .map(|ptr| new_package_id(env, ptr)),
+            destination: Some(d[4].clone()).filter(|s| !s.is_empty()),
+            replacing: std::iter::from_fn(|| paludis_package_ids_cursor_next(&replacing))
+                .map(|ptr| new_package_id(env, ptr))
+                .collect(),
+            reasons: lines(&d[5]),
+            required_confirmations: lines(&d[6]),
+        });
+    }
+
+    Ok(ResolutionPlan {
+        decisions,
+        jobs: new_jobs(paludis_resolution_jobs(&r, false)),
+        pretend_jobs: new_jobs(paludis_resolution_jobs(&r, true)),
+    })
+}
+
+fn lines(s: &str) -> Vec<String> {
+    s.lines().map(|l| l.to_owned()).collect()
+}
+
+/// Build jobs from groups of 5 strings: {kind, spec, destination, replaced specs, required job numbers}.
+pub(crate) fn new_jobs(raw: Vec<String>) -> Vec<Job> {
+    raw.chunks_exact(5)
+        .map(|j| Job {
+            kind: j[0].parse::<u8>().unwrap_or(u8::MAX).into(),
+            spec: j[1].clone(),
+            destination: j[2].clone(),
+            replacing: j[3].split_whitespace().map(|s| s.to_owned()).collect(),
+            requirements: j[4].split(',').filter_map(|n| n.parse().ok()).collect(),
+        })
+        .collect()
+}
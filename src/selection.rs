@@ -0,0 +1,145 @@
+use std::path::PathBuf;
+
+use super::bindings::paludis_environment_select;
+use super::bindings::paludis_filter_new;
+use super::bindings::paludis_filtered_generator_filter;
+use super::bindings::paludis_filtered_generator_new;
+use super::bindings::paludis_generator_intersection;
+use super::bindings::paludis_generator_new;
+
+use super::repository::new_package_ids;
+use super::ActionKind;
+use super::Environment;
+use super::PackageID;
+use super::Result;
+
+/// How the [`PackageID`]s found by a [`Query`] are selected and ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    /// Every matching ID, sorted by version.
+    AllVersionsSorted,
+    /// The best matching ID of each package.
+    BestVersionOnly,
+    /// The best matching ID of each slot of each package.
+    BestVersionInEachSlot,
+    /// Exactly one matching ID, otherwise fails with [`Error::DidNotGetExactlyOneError`](crate::Error::DidNotGetExactlyOneError).
+    RequireExactlyOne,
+    /// Any one matching ID of each package.
+    SomeArbitraryVersion,
+}
+
+impl Into<u8> for Selection {
+    fn into(self) -> u8 {
+        match self {
+            Selection::AllVersionsSorted => 0,
+            Selection::BestVersionOnly => 1,
+            Selection::BestVersionInEachSlot => 2,
+            Selection::RequireExactlyOne => 3,
+            Selection::SomeArbitraryVersion => 4,
+        }
+    }
+}
+
+/// Where the [`PackageID`]s of a [`Query`] are looked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Generator {
+    /// Every ID in every repository.
+    All,
+    /// IDs matching a user package dep spec, e.g. ">=dev-lang/rust-1.70:stable::arbor".
+    Matches(String),
+    /// IDs in the named repository.
+    InRepository(String),
+    /// IDs in the named category.
+    Category(String),
+}
+
+impl Generator {
+    fn kind(&self) -> (u8, &str) {
+        match self {
+            Generator::All => (0, ""),
+            Generator::Matches(spec) => (1, spec),
+            Generator::InRepository(repository) => (2, repository),
+            Generator::Category(category) => (3, category),
+        }
+    }
+}
+
+/// Restricts the [`PackageID`]s found by a [`Query`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    /// IDs that are not masked.
+    NotMasked,
+    /// IDs installed at the given root.
+    InstalledAtRoot(PathBuf),
+    /// IDs supporting the given action.
+    SupportsAction(ActionKind),
+}
+
+impl Filter {
+    fn kind(&self) -> (u8, String) {
+        match self {
+            Filter::NotMasked => (1, String::new()),
+            Filter::InstalledAtRoot(root) => (2, root.to_string_lossy().into_owned()),
+            Filter::SupportsAction(action) => (3 + Into::<u8>::into(*action), String::new()),
+        }
+    }
+}
+
+/// A query over every repository of an [`Environment`], see [query](Environment::query).
+///
+/// The IDs must match all the generators and pass all the filters.
+pub struct Query<'env> {
+    env: &'env Environment,
+    generators: Vec<Generator>,
+    filters: Vec<Filter>,
+}
+
+pub fn new_query(env: &Environment) -> Query<'_> {
+    Query {
+        env,
+        generators: Vec::new(),
+        filters: Vec::new(),
+    }
+}
+
+impl<'env> Query<'env> {
+    /// Also require the IDs to be generated by this generator.
+    pub fn generator(mut self, generator: Generator) -> Self {
+        self.generators.push(generator);
+        self
+    }
+
+    /// Also require the IDs to pass this filter.
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    /// Run the query and select the resulting IDs.
+    pub fn select(&self, selection: Selection) -> Result<Vec<PackageID<'env>>> {
+        let env = &self.env.ptr;
+
+        let (kind, arg) = Generator::All.kind();
+        let mut generator = paludis_generator_new(env, kind, arg)?;
+        for g in &self.generators {
+            let (kind, arg) = g.kind();
+            generator =
+                paludis_generator_intersection(&generator, &paludis_generator_new(env, kind, arg)?);
+        }
+
+        let mut filtered_generator = paludis_filtered_generator_new(&generator);
+        for f in &self.filters {
+            let (kind, arg) = f.kind();
+            filtered_generator = paludis_filtered_generator_filter(
+                &filtered_generator,
+                &paludis_filter_new(env, kind, &arg)?,
+            );
+        }
+
+        Ok(new_package_ids(
+            self.env,
+            paludis_environment_select(env, &filtered_generator, selection.into())?,
+        )
+        .collect())
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use cxx::SharedPtr;
+
+use super::bindings::paludis_environment_fetch_repository;
+use super::bindings::paludis_output_manager_flush;
+
+use super::environment::CreateOutputManagerSyncInfo;
+use super::environment::OuputExclusivity;
+use super::repository::new_repository;
+use super::Environment;
+use super::Result;
+use super::SyncResult;
+
+/// What [`Environment::sync_all`] syncs, like the arguments of `cave sync`.
+#[derive(Debug, Clone)]
+pub struct SyncPlan {
+    /// Names of the repositories to sync, all of them if empty.
+    pub repositories: Vec<String>,
+    /// Source to sync from, empty for the default one.
+    pub source: String,
+    /// Revision to sync to, empty for the latest one.
+    pub revision: String,
+    /// Maximum number of repositories synced at the same time.
+    pub jobs: usize,
+    /// How often the output of the running syncs is flushed.
+    pub flush_interval: Duration,
+    /// Show a summary of the messages of each sync once it is done.
+    pub summary: bool,
+}
+
+impl Default for SyncPlan {
+    fn default() -> Self {
+        SyncPlan {
+            repositories: Vec::new(),
+            source: String::new(),
+            revision: String::new(),
+            jobs: 5,
+            flush_interval: Duration::from_secs(1),
+            summary: false,
+        }
+    }
+}
+
+/// What happened to a repository during [`Environment::sync_all`].
+#[derive(Debug, Clone)]
+pub struct SyncReport {
+    pub repository: String,
+    /// Time spent waiting for the master repositories and a free job.
+    pub waited: Duration,
+    /// Time spent syncing.
+    pub duration: Duration,
+    pub result: Result<SyncResult>,
+}
+
+struct State {
+    pending: Vec<String>,
+    running: Vec<(String, SharedPtr<u64>)>,
+    done: HashSet<String>,
+    reports: Vec<SyncReport>,
+}
+
+/// Sync repositories in parallel, each one after its master repositories.
+pub fn sync_all(env: &Environment, plan: &SyncPlan) -> Result<Vec<SyncReport>> {
+    let mut names: Vec<String> = Vec::new();
+    if plan.repositories.is_empty() {
+        names = env.repositories_names();
+    } else {
+        for name in &plan.repositories {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+
+    let masters_of = |name: &String| -> Result<Vec<String>> {
+        let repository = new_repository(env, paludis_environment_fetch_repository(&env.ptr, name)?);
+        Ok(repository
+            .master_repositories_names()?
+            .into_iter()
+            .filter(|m| m != name && names.contains(m))
+            .collect())
+    };
+
+    // Masters of each repository, restricted to the ones being synced.
+    // Unknown repositories are reported as failed without stopping the others.
+    let mut masters = Vec::new();
+    let mut state = State {
+        pending: Vec::new(),
+        running: Vec::new(),
+        done: HashSet::new(),
+        reports: Vec::new(),
+    };
+    for name in &names {
+        match masters_of(name) {
+            Ok(m) => {
+                masters.push((name.clone(), m));
+                state.pending.push(name.clone());
+            }
+            Err(e) => {
+                state.reports.push(SyncReport {
+                    repository: name.clone(),
+                    waited: Duration::ZERO,
+                    duration: Duration::ZERO,
+                    result: Err(e),
+                });
+                state.done.insert(name.clone());
+            }
+        }
+    }
+
+    let state = Mutex::new(state);
+    let changed = Condvar::new();
+    let start = Instant::now();
+
+    let worker = || loop {
+        let (name, mut om) = {
+            let mut s = state.lock().unwrap();
+            let name = loop {
+                if s.pending.is_empty() {
+                    return;
+                }
+                let ready = s.pending.iter().position(|n| {
+                    masters
+                        .iter()
+                        .find(|(r, _)| r == n)
+                        .is_none_or(|(_, m)| m.iter().all(|m| s.done.contains(m)))
+                });
+                // Masters depending on each other can't be ordered, sync them anyway
+                match ready.or(if s.running.is_empty() { Some(0) } else { None }) {
+                    Some(i) => break s.pending.remove(i),
+                    None => s = changed.wait(s).unwrap(),
+                }
+            };
+
+            match env.create_sync_output_manager(CreateOutputManagerSyncInfo {
+                repository: name.clone(),
+                ouput_exculivity: OuputExclusivity::WithOthers,
+                summary: plan.summary,
+            }) {
+                Ok(om) => {
+                    s.running.push((name.clone(), om.ptr.clone()));
+                    (name, om)
+                }
+                Err(e) => {
+                    s.reports.push(SyncReport {
+                        repository: name.clone(),
+                        waited: start.elapsed(),
+                        duration: Duration::ZERO,
+                        result: Err(e),
+                    });
+                    s.done.insert(name);
+                    changed.notify_all();
+                    continue;
+                }
+            }
+        };
+
+        let waited = start.elapsed();
+        let result = match env.fetch_repository(&name) {
+            Some(mut repository) => {
+                repository.sync_to_revision(&plan.source, &plan.revision, &mut om)
+            }
+            None => Ok(SyncResult::Skipped),
+        };
+
+        let mut s = state.lock().unwrap();
+        s.running.retain(|(n, _)| n != &name);
+        s.reports.push(SyncReport {
+            repository: name.clone(),
+            waited,
+            duration: start.elapsed() - waited,
+            result,
+        });
+        s.done.insert(name);
+        changed.notify_all();
+    };
+
+    std::thread::scope(|scope| {
+        for _ in 0..plan.jobs.max(1).min(names.len()) {
+            scope.spawn(worker);
+        }
+
+        // Show the buffered output of the running syncs until they are all done
+        let mut s = state.lock().unwrap();
+        while s.done.len() < names.len() {
+            for (_, ptr) in &s.running {
+                paludis_output_manager_flush(ptr.clone());
+            }
+            s = changed.wait_timeout(s, plan.flush_interval).unwrap().0;
+        }
+    });
+
+    let mut reports = state.into_inner().unwrap().reports;
+    reports.sort_by_key(|r| names.iter().position(|n| n == &r.repository));
+    Ok(reports)
+}
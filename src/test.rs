@@ -1,2 +1,254 @@
 #[cfg(test)]
-mod test {}
+mod test {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::time::Duration;
+    use std::time::SystemTime;
+
+    use crate::contents::new_contents;
+    use crate::dep_spec::parse_choice_requirement;
+    use crate::execute::skipped;
+    use crate::logging::parse_message;
+    use crate::logging::LogLevel;
+    use crate::resolver::new_jobs;
+    use crate::ChoiceRequirementKind;
+    use crate::ContentsEntry;
+    use crate::ContinueOnFailure;
+    use crate::DecisionKind;
+    use crate::Job;
+    use crate::JobKind;
+    use crate::JobState;
+
+    #[test]
+    fn parse_log_message_with_context() {
+        let m = parse_message(
+            "cave@1700000000: [WARNING e.ebuild.unknown_eapi] When loading 'foo':\n  ... When parsing EAPI:\n  ... Unknown EAPI '9'\n",
+        )
+        .unwrap();
+        assert_eq!(m.level, LogLevel::Warning);
+        assert_eq!(m.id, "e.ebuild.unknown_eapi");
+        assert_eq!(m.context, vec!["When loading 'foo':", "When parsing EAPI:"]);
+        assert_eq!(m.message, "Unknown EAPI '9'");
+    }
+
+    #[test]
+    fn parse_log_message_without_context() {
+        let m = parse_message("cave@1700000000: [QA e.qa.foo] Something is odd\n").unwrap();
+        assert_eq!(m.level, LogLevel::Qa);
+        assert_eq!(m.id, "e.qa.foo");
+        assert!(m.context.is_empty());
+        assert_eq!(m.message, "Something is odd");
+    }
+
+    #[test]
+    fn parse_log_message_not_matching() {
+        assert_eq!(parse_message("not a paludis log line\n"), None);
+    }
+
+    #[test]
+    fn parse_choice_requirements() {
+        let cases = [
+            ("llvm", "llvm", ChoiceRequirementKind::Enabled, None),
+            ("-doc", "doc", ChoiceRequirementKind::Disabled, None),
+            (
+                "-doc(+)",
+                "doc",
+                ChoiceRequirementKind::Disabled,
+                Some(true),
+            ),
+            ("llvm?", "llvm", ChoiceRequirementKind::IfEnabled, None),
+            (
+                "!doc(-)?",
+                "doc",
+                ChoiceRequirementKind::IfDisabled,
+                Some(false),
+            ),
+            (
+                "python_abis:3.11=",
+                "python_abis:3.11",
+                ChoiceRequirementKind::Equal,
+                None,
+            ),
+            ("!llvm=", "llvm", ChoiceRequirementKind::NotEqual, None),
+        ];
+        for (raw, name, kind, default) in cases {
+            let r = parse_choice_requirement(raw).unwrap();
+            assert_eq!(
+                (r.name.as_str(), r.kind, r.default),
+                (name, kind, default),
+                "{}",
+                raw
+            );
+        }
+
+        for raw in ["", "!llvm", "-llvm?", ".SLOT=stable", "a,b", "(+)"] {
+            assert_eq!(parse_choice_requirement(raw), None, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn decode_decision_kinds() {
+        let kinds: Vec<DecisionKind> = (0..6).map(DecisionKind::from).collect();
+        assert_eq!(
+            kinds,
+            [
+                DecisionKind::ChangeOrInstall,
+                DecisionKind::Keep,
+                DecisionKind::Remove,
+                DecisionKind::Break,
+                DecisionKind::Unable,
+                DecisionKind::Unable,
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_jobs() {
+        let raw = [
+            ["0", "=dev-lang/rust-1.70.0:stable::arbor", "", "", ""],
+            [
+                "1",
+                "=dev-lang/rust-1.70.0:stable::arbor",
+                "installed",
+                "=dev-lang/rust-1.69.0:stable::installed",
+                "0",
+            ],
+            ["2", "=a/b-1::installed =a/c-1::installed", "", "", "0,1"],
+            ["3", "=a/d-1::arbor", "installed", "", ""],
+        ];
+        let jobs = new_jobs(raw.iter().flatten().map(|s| s.to_string()).collect());
+
+        assert_eq!(
+            jobs,
+            [
+                Job {
+                    kind: JobKind::Fetch,
+                    spec: "=dev-lang/rust-1.70.0:stable::arbor".to_owned(),
+                    destination: String::new(),
+                    replacing: Vec::new(),
+                    requirements: Vec::new(),
+                },
+                Job {
+                    kind: JobKind::Install,
+                    spec: "=dev-lang/rust-1.70.0:stable::arbor".to_owned(),
+                    destination: "installed".to_owned(),
+                    replacing: vec!["=dev-lang/rust-1.69.0:stable::installed".to_owned()],
+                    requirements: vec![0],
+                },
+                Job {
+                    kind: JobKind::Uninstall,
+                    spec: "=a/b-1::installed =a/c-1::installed".to_owned(),
+                    destination: String::new(),
+                    replacing: Vec::new(),
+                    requirements: vec![0, 1],
+                },
+                Job {
+                    kind: JobKind::Pretend,
+                    spec: "=a/d-1::arbor".to_owned(),
+                    destination: "installed".to_owned(),
+                    replacing: Vec::new(),
+                    requirements: Vec::new(),
+                },
+            ]
+        );
+
+        // An incomplete group is ignored
+        assert_eq!(
+            new_jobs(vec!["0".to_owned(), "=a/b-1::arbor".to_owned()]),
+            []
+        );
+    }
+
+    #[test]
+    fn skip_jobs_after_failure() {
+        let job = |kind, requirements: Vec<usize>| Job {
+            kind,
+            spec: String::new(),
+            destination: String::new(),
+            replacing: Vec::new(),
+            requirements,
+        };
+        let jobs = [
+            job(JobKind::Fetch, vec![]),
+            job(JobKind::Fetch, vec![]),
+            job(JobKind::Install, vec![0]),
+            job(JobKind::Install, vec![1]),
+        ];
+        let failed = Some(JobState::Failed {
+            message: "fetch failed".to_owned(),
+        });
+        let states = [failed, Some(JobState::Succeeded), None, None];
+
+        // Whether jobs 1, 2 and 3 are skipped once job 0 failed
+        let table = [
+            (ContinueOnFailure::Never, [true, true, true]),
+            (ContinueOnFailure::IfFetching, [false, true, true]),
+            (ContinueOnFailure::IfIndependent, [false, true, false]),
+            (ContinueOnFailure::Always, [false, false, false]),
+        ];
+        for (policy, expected) in table {
+            let skips: Vec<bool> = jobs[1..]
+                .iter()
+                .map(|j| skipped(j, &states, policy, &jobs))
+                .collect();
+            assert_eq!(skips, expected, "{:?}", policy);
+        }
+
+        // A failure other than a fetch stops fetching too
+        let states = [None, None, Some(JobState::Skipped), states[0].clone()];
+        let jobs = [
+            job(JobKind::Fetch, vec![]),
+            job(JobKind::Fetch, vec![]),
+            job(JobKind::Install, vec![]),
+            job(JobKind::Install, vec![]),
+        ];
+        assert!(skipped(
+            &jobs[0],
+            &states,
+            ContinueOnFailure::IfFetching,
+            &jobs
+        ));
+        assert!(!skipped(
+            &jobs[0],
+            &states,
+            ContinueOnFailure::IfIndependent,
+            &jobs
+        ));
+    }
+
+    #[test]
+    fn decode_contents_with_non_utf8_paths() {
+        let raw: Vec<Vec<u8>> = vec![
+            b"0".to_vec(),
+            b"/usr/share/doc/caf\xe9".to_vec(),
+            b"1700000000".to_vec(),
+            b"5".to_vec(),
+            b"d41d8cd98f00b204e9800998ecf8427e".to_vec(),
+            b"".to_vec(),
+            b"2".to_vec(),
+            b"/usr/bin/l\xff".to_vec(),
+            b"".to_vec(),
+            b"".to_vec(),
+            b"".to_vec(),
+            b"t\xfe".to_vec(),
+        ];
+        let contents = new_contents(raw);
+
+        assert_eq!(
+            contents,
+            [
+                ContentsEntry::File {
+                    path: Path::new(OsStr::from_bytes(b"/usr/share/doc/caf\xe9")).to_owned(),
+                    mtime: Some(SystemTime::UNIX_EPOCH + Duration::new(1_700_000_000, 5)),
+                    md5: Some("d41d8cd98f00b204e9800998ecf8427e".to_owned()),
+                },
+                ContentsEntry::Sym {
+                    path: Path::new(OsStr::from_bytes(b"/usr/bin/l\xff")).to_owned(),
+                    target: Path::new(OsStr::from_bytes(b"t\xfe")).to_owned(),
+                    mtime: None,
+                },
+            ]
+        );
+    }
+}